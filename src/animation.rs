@@ -0,0 +1,405 @@
+use crate::bindings::screen_closed;
+use crate::components::{FontSpec, RunState};
+use crate::storage::{load_json, save_json};
+use crate::theme::Theme;
+use bevy::prelude::*;
+use bevy::transform::TransformSystem;
+use bevy_easings::*;
+use serde::{Deserialize, Serialize};
+use std::{f32::consts::PI, time::Duration};
+
+const SETTINGS_FILE: &str = "animation.json";
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AnimationSpeed {
+    #[default]
+    Normal,
+    Fast,
+    /// Tiles jump to their place, nothing is animated.
+    Instant,
+}
+
+impl AnimationSpeed {
+    pub const ALL: [AnimationSpeed; 3] = [
+        AnimationSpeed::Normal,
+        AnimationSpeed::Fast,
+        AnimationSpeed::Instant,
+    ];
+
+    /// The speed after this one, wrapping around.
+    pub fn next(self) -> Self {
+        let index = AnimationSpeed::ALL
+            .iter()
+            .position(|speed| *speed == self)
+            .map_or(0, |index| (index + 1) % AnimationSpeed::ALL.len());
+        AnimationSpeed::ALL[index]
+    }
+
+    fn factor(self) -> f32 {
+        match self {
+            AnimationSpeed::Normal => 1.0,
+            AnimationSpeed::Fast => 0.5,
+            AnimationSpeed::Instant => 0.0,
+        }
+    }
+}
+
+/// Easing of the tile slides.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Easing {
+    Linear,
+    #[default]
+    QuadraticInOut,
+    CubicInOut,
+    SineInOut,
+    BackOut,
+}
+
+impl Easing {
+    pub const ALL: [Easing; 5] = [
+        Easing::Linear,
+        Easing::QuadraticInOut,
+        Easing::CubicInOut,
+        Easing::SineInOut,
+        Easing::BackOut,
+    ];
+
+    pub fn method(self) -> EaseMethod {
+        match self {
+            Easing::Linear => EaseMethod::Linear,
+            Easing::QuadraticInOut => EaseFunction::QuadraticInOut.into(),
+            Easing::CubicInOut => EaseFunction::CubicInOut.into(),
+            Easing::SineInOut => EaseFunction::SineInOut.into(),
+            Easing::BackOut => EaseFunction::BackOut.into(),
+        }
+    }
+}
+
+#[derive(Resource, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AnimationSettings {
+    pub speed: AnimationSpeed,
+    pub easing: Easing,
+    /// Turns off score popups, merge pulses, the score count-up and shakes.
+    pub reduced_motion: bool,
+    /// Durations at normal speed, in milliseconds.
+    pub slide_ms: u64,
+    pub spawn_ms: u64,
+    pub merge_ms: u64,
+    /// How much larger a merged tile gets at the top of its pulse.
+    pub merge_pulse: f32,
+}
+
+impl Default for AnimationSettings {
+    fn default() -> Self {
+        AnimationSettings {
+            speed: AnimationSpeed::default(),
+            easing: Easing::default(),
+            reduced_motion: false,
+            slide_ms: 100,
+            spawn_ms: 150,
+            merge_ms: 120,
+            merge_pulse: 0.2,
+        }
+    }
+}
+
+impl AnimationSettings {
+    fn scaled(&self, millis: u64) -> Duration {
+        Duration::from_millis(millis).mul_f32(self.speed.factor())
+    }
+
+    pub fn instant(&self) -> bool {
+        self.speed == AnimationSpeed::Instant
+    }
+
+    pub fn slide(&self) -> Duration {
+        self.scaled(self.slide_ms)
+    }
+
+    pub fn spawn(&self) -> Duration {
+        self.scaled(self.spawn_ms)
+    }
+
+    pub fn merge(&self) -> Duration {
+        self.scaled(self.merge_ms)
+    }
+
+    pub fn score_popup(&self) -> Duration {
+        self.scaled(600)
+    }
+
+    /// Roughly how long the score takes to count up to a new value.
+    pub fn score_count(&self) -> Duration {
+        self.scaled(300)
+    }
+
+    /// Whether merges pulse and score popups and the count-up are shown.
+    pub fn flourishes(&self) -> bool {
+        !self.instant() && !self.reduced_motion
+    }
+}
+
+/// Scale of a tile, kept apart from `Transform` so it can be eased
+/// while the slide eases the position.
+#[derive(Component, Debug, Clone, Copy)]
+pub struct TileScale {
+    /// 0 is invisible, 1 is full size.
+    pub grow: f32,
+    /// Progress through a merge pulse, the tile is full size at 0 and 1.
+    pub pulse: f32,
+}
+
+impl Default for TileScale {
+    fn default() -> Self {
+        TileScale {
+            grow: 1.0,
+            pulse: 0.0,
+        }
+    }
+}
+
+impl Lerp for TileScale {
+    type Scalar = f32;
+
+    fn lerp(&self, other: &Self, scalar: &Self::Scalar) -> Self {
+        TileScale {
+            grow: self.grow + (other.grow - self.grow) * scalar,
+            pulse: self.pulse + (other.pulse - self.pulse) * scalar,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TileAnimation {
+    Spawn,
+    Merge,
+}
+
+/// Animation that starts once the tiles finished sliding.
+#[derive(Component)]
+pub struct PendingAnimation {
+    pub animation: TileAnimation,
+    pub delay: Timer,
+}
+
+impl PendingAnimation {
+    pub fn after_slide(animation: TileAnimation, settings: &AnimationSettings) -> Self {
+        PendingAnimation {
+            animation,
+            delay: Timer::new(settings.slide(), TimerMode::Once),
+        }
+    }
+}
+
+/// A tile that merged into another one, removed once it slid underneath.
+#[derive(Component)]
+pub struct Absorbed {
+    pub despawn: Timer,
+}
+
+impl Absorbed {
+    pub fn after_slide(settings: &AnimationSettings) -> Self {
+        Absorbed {
+            despawn: Timer::new(settings.slide(), TimerMode::Once),
+        }
+    }
+}
+
+/// Points a move scored, shown as "+N" rising from where the tiles merged.
+#[derive(Event)]
+pub struct ScorePopupEvent {
+    pub points: u32,
+    pub position: Vec2,
+}
+
+#[derive(Component)]
+pub struct ScorePopup {
+    pub timer: Timer,
+    pub start: Vec2,
+}
+
+pub struct AnimationPlugin;
+impl Plugin for AnimationPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<AnimationSettings>()
+            .add_event::<ScorePopupEvent>()
+            .add_systems(Startup, load_animation_settings)
+            .add_systems(
+                Update,
+                (
+                    animation_keys.run_if(screen_closed),
+                    // Their timers stand still while the game is paused
+                    (
+                        (start_tile_animations, custom_ease_system::<TileScale>).chain(),
+                        despawn_absorbed_tiles,
+                        (spawn_score_popups, float_score_popups),
+                    )
+                        .run_if(not(in_state(RunState::Paused))),
+                ),
+            )
+            .add_systems(OnEnter(RunState::Paused), pause_easings)
+            .add_systems(OnExit(RunState::Paused), resume_easings)
+            .add_systems(
+                PostUpdate,
+                apply_tile_scale.before(TransformSystem::TransformPropagate),
+            );
+    }
+}
+
+fn load_animation_settings(mut settings: ResMut<AnimationSettings>) {
+    *settings = load_json(SETTINGS_FILE);
+}
+
+pub fn save_animation_settings(settings: &AnimationSettings) {
+    if let Err(err) = save_json(SETTINGS_FILE, settings) {
+        error!("Failed to save the animation settings: {}", err);
+    }
+}
+
+// F3 switches the speed, F4 reduced motion.
+fn animation_keys(input: Res<Input<KeyCode>>, mut settings: ResMut<AnimationSettings>) {
+    if input.just_pressed(KeyCode::F3) {
+        settings.speed = settings.speed.next();
+        info!("Animation speed: {:?}", settings.speed);
+    } else if input.just_pressed(KeyCode::F4) {
+        settings.reduced_motion = !settings.reduced_motion;
+        info!("Reduced motion: {}", settings.reduced_motion);
+    } else {
+        return;
+    }
+    save_animation_settings(&settings);
+}
+
+fn pause_easings(
+    mut slides: Query<&mut EasingComponent<Transform>>,
+    mut scales: Query<&mut EasingComponent<TileScale>>,
+) {
+    for mut easing in slides.iter_mut() {
+        easing.state = EasingState::Paused;
+    }
+    for mut easing in scales.iter_mut() {
+        easing.state = EasingState::Paused;
+    }
+}
+
+fn resume_easings(
+    mut slides: Query<&mut EasingComponent<Transform>>,
+    mut scales: Query<&mut EasingComponent<TileScale>>,
+) {
+    for mut easing in slides.iter_mut() {
+        easing.state = EasingState::Play;
+    }
+    for mut easing in scales.iter_mut() {
+        easing.state = EasingState::Play;
+    }
+}
+
+fn start_tile_animations(
+    mut commands: Commands,
+    time: Res<Time>,
+    settings: Res<AnimationSettings>,
+    mut tiles: Query<(Entity, &mut PendingAnimation)>,
+) {
+    for (entity, mut pending) in tiles.iter_mut() {
+        if !pending.delay.tick(time.delta()).finished() {
+            continue;
+        }
+        let (from, to, duration) = match pending.animation {
+            TileAnimation::Spawn => (
+                TileScale { grow: 0.0, pulse: 0.0 },
+                TileScale::default(),
+                settings.spawn(),
+            ),
+            TileAnimation::Merge => (
+                TileScale::default(),
+                TileScale { grow: 1.0, pulse: 1.0 },
+                settings.merge(),
+            ),
+        };
+        commands
+            .entity(entity)
+            .remove::<PendingAnimation>()
+            .insert(from)
+            .insert(from.ease_to(
+                to,
+                EaseFunction::QuadraticOut,
+                EasingType::Once { duration },
+            ));
+    }
+}
+
+fn despawn_absorbed_tiles(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut tiles: Query<(Entity, &mut Absorbed)>,
+) {
+    for (entity, mut absorbed) in tiles.iter_mut() {
+        if absorbed.despawn.tick(time.delta()).finished() {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
+
+fn spawn_score_popups(
+    mut commands: Commands,
+    mut popup_reader: EventReader<ScorePopupEvent>,
+    font_spec: Res<FontSpec>,
+    settings: Res<AnimationSettings>,
+    theme: Res<Theme>,
+) {
+    for event in popup_reader.iter() {
+        if !settings.flourishes() {
+            continue;
+        }
+        commands.spawn((
+            Text2dBundle {
+                text: Text::from_section(
+                    format!("+{}", event.points),
+                    TextStyle {
+                        font: font_spec.family.clone(),
+                        font_size: 30.0,
+                        color: theme.text,
+                    },
+                )
+                .with_alignment(TextAlignment::Center),
+                transform: Transform::from_translation(event.position.extend(10.0)),
+                ..default()
+            },
+            ScorePopup {
+                timer: Timer::new(settings.score_popup(), TimerMode::Once),
+                start: event.position,
+            },
+        ));
+    }
+}
+
+fn float_score_popups(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut popups: Query<(Entity, &mut ScorePopup, &mut Transform, &mut Text)>,
+) {
+    for (entity, mut popup, mut transform, mut text) in popups.iter_mut() {
+        popup.timer.tick(time.delta());
+        if popup.timer.finished() {
+            commands.entity(entity).despawn_recursive();
+            continue;
+        }
+        let progress = popup.timer.percent();
+        transform.translation.y = popup.start.y + 50.0 * progress;
+        for section in text.sections.iter_mut() {
+            section.style.color.set_a(1.0 - progress);
+        }
+    }
+}
+
+// Runs after the slide easing so it doesn't reset the scale.
+fn apply_tile_scale(
+    settings: Res<AnimationSettings>,
+    mut tiles: Query<(&TileScale, &mut Transform)>,
+) {
+    for (scale, mut transform) in tiles.iter_mut() {
+        let pulse = 1.0 + settings.merge_pulse * (scale.pulse * PI).sin();
+        transform.scale = Vec3::splat(scale.grow * pulse);
+    }
+}
@@ -0,0 +1,438 @@
+use crate::components::{FontSpec, RunState};
+use crate::confirm::{no_confirmation, Abandon, AbandonRequest};
+use crate::leaderboard::not_typing;
+use crate::storage::{load_json, save_json};
+use crate::styles::{overlay_root, small_button_style};
+use crate::theme::{Theme, ThemeColor};
+use crate::timeline::UndoEvent;
+use crate::ui::spawn_button;
+use crate::utility::{BoardShift, NewGameEvent};
+use bevy::input::{keyboard::KeyboardInput, ButtonState};
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+const BINDINGS_FILE: &str = "keybindings.json";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Action {
+    ShiftLeft,
+    ShiftRight,
+    ShiftUp,
+    ShiftDown,
+    Restart,
+    Undo,
+    Pause,
+    Confirm,
+}
+
+impl Action {
+    pub const ALL: [Action; 8] = [
+        Action::ShiftLeft,
+        Action::ShiftRight,
+        Action::ShiftUp,
+        Action::ShiftDown,
+        Action::Restart,
+        Action::Undo,
+        Action::Pause,
+        Action::Confirm,
+    ];
+
+    pub fn board_shift(&self) -> Option<BoardShift> {
+        match self {
+            Action::ShiftLeft => Some(BoardShift::Left),
+            Action::ShiftRight => Some(BoardShift::Right),
+            Action::ShiftUp => Some(BoardShift::Up),
+            Action::ShiftDown => Some(BoardShift::Down),
+            _ => None,
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Action::ShiftLeft => "Shift left",
+            Action::ShiftRight => "Shift right",
+            Action::ShiftUp => "Shift up",
+            Action::ShiftDown => "Shift down",
+            Action::Restart => "Restart",
+            Action::Undo => "Undo",
+            Action::Pause => "Pause",
+            Action::Confirm => "Confirm",
+        }
+    }
+}
+
+#[derive(Event, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ActionEvent(pub Action);
+
+#[derive(Resource, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct KeyBindings {
+    pub bindings: Vec<(KeyCode, Action)>,
+}
+
+impl Default for KeyBindings {
+    fn default() -> Self {
+        use Action::*;
+        KeyBindings {
+            bindings: vec![
+                (KeyCode::Left, ShiftLeft),
+                (KeyCode::Right, ShiftRight),
+                (KeyCode::Up, ShiftUp),
+                (KeyCode::Down, ShiftDown),
+                (KeyCode::A, ShiftLeft),
+                (KeyCode::D, ShiftRight),
+                (KeyCode::W, ShiftUp),
+                (KeyCode::S, ShiftDown),
+                (KeyCode::H, ShiftLeft),
+                (KeyCode::L, ShiftRight),
+                (KeyCode::K, ShiftUp),
+                (KeyCode::J, ShiftDown),
+                (KeyCode::R, Restart),
+                (KeyCode::U, Undo),
+                (KeyCode::Back, Undo),
+                (KeyCode::P, Pause),
+                (KeyCode::Return, Confirm),
+            ],
+        }
+    }
+}
+
+impl KeyBindings {
+    pub fn action(&self, key: KeyCode) -> Option<Action> {
+        self.bindings
+            .iter()
+            .find(|(bound, _)| *bound == key)
+            .map(|(_, action)| *action)
+    }
+
+    pub fn keys(&self, action: Action) -> impl Iterator<Item = KeyCode> + '_ {
+        self.bindings
+            .iter()
+            .filter(move |(_, bound)| *bound == action)
+            .map(|(key, _)| *key)
+    }
+
+    /// Keys bound to more than one action.
+    pub fn conflicts(&self) -> Vec<KeyCode> {
+        let mut conflicts: Vec<KeyCode> = self
+            .bindings
+            .iter()
+            .filter(|(key, action)| self.bindings.iter().any(|(k, a)| k == key && a != action))
+            .map(|(key, _)| *key)
+            .collect();
+        conflicts.sort();
+        conflicts.dedup();
+        conflicts
+    }
+
+    /// Binds `key` to `action`, refusing keys already used by another action.
+    pub fn bind(&mut self, key: KeyCode, action: Action) -> Result<(), Action> {
+        match self.action(key) {
+            Some(bound) if bound != action => Err(bound),
+            Some(_) => Ok(()),
+            None => {
+                self.bindings.push((key, action));
+                Ok(())
+            }
+        }
+    }
+
+    pub fn clear(&mut self, action: Action) {
+        self.bindings.retain(|(_, bound)| *bound != action);
+    }
+}
+
+#[derive(Default, Resource)]
+pub struct BindingScreen {
+    pub open: bool,
+    /// Action waiting for a key press to be bound.
+    pub waiting: Option<Action>,
+    pub message: String,
+}
+
+pub fn screen_closed(screen: Res<BindingScreen>) -> bool {
+    !screen.open
+}
+
+#[derive(Component)]
+pub struct BindingScreenRoot;
+
+#[derive(Component)]
+pub struct BindingList;
+
+#[derive(Component)]
+pub enum BindingButton {
+    Rebind(Action),
+    Defaults,
+    Close,
+}
+
+pub struct BindingsPlugin;
+impl Plugin for BindingsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<BindingScreen>()
+            .add_event::<ActionEvent>()
+            .add_systems(Startup, (load_bindings, setup_binding_screen))
+            .add_systems(
+                PreUpdate,
+                keyboard_actions
+                    .run_if(screen_closed)
+                    .run_if(not_typing)
+                    .run_if(no_confirmation)
+                    .after(bevy::input::InputSystem),
+            )
+            .add_systems(
+                Update,
+                (
+                    handle_actions,
+                    toggle_binding_screen,
+                    binding_buttons,
+                    capture_binding.run_if(not(screen_closed)),
+                    render_binding_screen,
+                )
+                    .chain(),
+            );
+    }
+}
+
+fn load_bindings(mut commands: Commands) {
+    let bindings = load_json::<KeyBindings>(BINDINGS_FILE);
+    for key in bindings.conflicts() {
+        warn!("{:?} is bound to more than one action", key);
+    }
+    commands.insert_resource(bindings);
+}
+
+fn save_bindings(bindings: &KeyBindings) {
+    if let Err(err) = save_json(BINDINGS_FILE, bindings) {
+        error!("Failed to save key bindings: {}", err);
+    }
+}
+
+// Reads the key events rather than `Input` to keep the order of keys
+// pressed in the same frame, `just_pressed` filters out key repeats.
+pub fn keyboard_actions(
+    mut key_events: EventReader<KeyboardInput>,
+    input: Res<Input<KeyCode>>,
+    bindings: Res<KeyBindings>,
+    mut action_writer: EventWriter<ActionEvent>,
+) {
+    let mut pressed = Vec::new();
+    for event in key_events.iter() {
+        let Some(key) = event.key_code else {
+            continue;
+        };
+        if event.state == ButtonState::Pressed && input.just_pressed(key) && !pressed.contains(&key) {
+            pressed.push(key);
+            if let Some(action) = bindings.action(key) {
+                action_writer.send(ActionEvent(action));
+            }
+        }
+    }
+}
+
+fn handle_actions(
+    mut action_reader: EventReader<ActionEvent>,
+    run_state: Res<State<RunState>>,
+    mut new_game_writer: EventWriter<NewGameEvent>,
+    mut undo_writer: EventWriter<UndoEvent>,
+    mut abandon_writer: EventWriter<AbandonRequest>,
+    mut next_state: ResMut<NextState<RunState>>,
+) {
+    for ActionEvent(action) in action_reader.iter() {
+        match (action, run_state.get()) {
+            (Action::Pause, RunState::Playing) => next_state.set(RunState::Paused),
+            (Action::Pause | Action::Confirm, RunState::Paused) => next_state.set(RunState::Playing),
+            (Action::Restart, RunState::Playing) => abandon_writer.send(AbandonRequest(Abandon::Restart)),
+            (Action::Restart | Action::Confirm, RunState::GameOver) => new_game_writer.send(NewGameEvent),
+            (Action::Undo, RunState::Playing | RunState::GameOver) => undo_writer.send(UndoEvent),
+            _ => {}
+        }
+    }
+}
+
+fn toggle_binding_screen(input: Res<Input<KeyCode>>, mut screen: ResMut<BindingScreen>) {
+    if input.just_pressed(KeyCode::F1) {
+        screen.open = !screen.open;
+        screen.waiting = None;
+        screen.message.clear();
+    }
+}
+
+fn binding_buttons(
+    mut interaction_query: Query<
+        (&Interaction, &mut BackgroundColor, &BindingButton),
+        Changed<Interaction>,
+    >,
+    mut screen: ResMut<BindingScreen>,
+    mut bindings: ResMut<KeyBindings>,
+    theme: Res<Theme>,
+) {
+    for (interaction, mut color, button) in interaction_query.iter_mut() {
+        *color = theme.button_color(*interaction);
+        match interaction {
+            Interaction::Pressed => {
+                match button {
+                    BindingButton::Rebind(action) => {
+                        screen.waiting = Some(*action);
+                        screen.message = format!(
+                            "Press a key for {} (Esc cancels, Delete clears)",
+                            action.label()
+                        );
+                    }
+                    BindingButton::Defaults => {
+                        *bindings = KeyBindings::default();
+                        save_bindings(&bindings);
+                        screen.message = "Restored the default bindings".to_string();
+                    }
+                    BindingButton::Close => {
+                        screen.open = false;
+                        screen.waiting = None;
+                    }
+                }
+            }
+            Interaction::Hovered | Interaction::None => {}
+        }
+    }
+}
+
+fn capture_binding(
+    input: Res<Input<KeyCode>>,
+    mut screen: ResMut<BindingScreen>,
+    mut bindings: ResMut<KeyBindings>,
+) {
+    let Some(action) = screen.waiting else {
+        if input.just_pressed(KeyCode::Escape) {
+            screen.open = false;
+        }
+        return;
+    };
+    let Some(key) = input.get_just_pressed().next().copied() else {
+        return;
+    };
+    screen.waiting = None;
+    screen.message = match key {
+        KeyCode::Escape => String::new(),
+        KeyCode::Delete => {
+            bindings.clear(action);
+            save_bindings(&bindings);
+            format!("Cleared the keys of {}", action.label())
+        }
+        key => match bindings.bind(key, action) {
+            Ok(()) => {
+                save_bindings(&bindings);
+                format!("Bound {:?} to {}", key, action.label())
+            }
+            Err(bound) => format!("{:?} is already bound to {}", key, bound.label()),
+        },
+    };
+}
+
+fn render_binding_screen(
+    mut commands: Commands,
+    screen: Res<BindingScreen>,
+    bindings: Res<KeyBindings>,
+    mut root_query: Query<&mut Style, With<BindingScreenRoot>>,
+    list_query: Query<Entity, With<BindingList>>,
+    font_spec: Res<FontSpec>,
+) {
+    if !screen.is_changed() && !bindings.is_changed() {
+        return;
+    }
+    root_query.single_mut().display = if screen.open {
+        Display::Flex
+    } else {
+        Display::None
+    };
+
+    let conflicts = bindings.conflicts();
+    let list = list_query.single();
+    commands.entity(list).despawn_descendants();
+    commands.entity(list).with_children(|parent| {
+        for action in Action::ALL {
+            let keys = bindings
+                .keys(action)
+                .map(|key| {
+                    if conflicts.contains(&key) {
+                        format!("{:?}!", key)
+                    } else {
+                        format!("{:?}", key)
+                    }
+                })
+                .collect::<Vec<_>>()
+                .join(", ");
+            spawn_button(
+                parent,
+                &font_spec,
+                format!("{}: {}", action.label(), keys),
+                BindingButton::Rebind(action),
+                small_button_style(),
+            );
+        }
+        spawn_button(parent, &font_spec, "Defaults", BindingButton::Defaults, small_button_style());
+        spawn_button(parent, &font_spec, "Close", BindingButton::Close, small_button_style());
+
+        let message = if conflicts.is_empty() {
+            screen.message.clone()
+        } else {
+            format!("{}\nKeys marked ! are bound twice", screen.message)
+        };
+        parent.spawn((
+            TextBundle::from_section(
+                message,
+                TextStyle {
+                    font: font_spec.family.clone(),
+                    font_size: 15.0,
+                    color: Color::WHITE,
+                },
+            ),
+            ThemeColor::Text,
+        ));
+    });
+}
+
+fn setup_binding_screen(mut commands: Commands, font_spec: Res<FontSpec>) {
+    commands
+        .spawn((
+            overlay_root(10),
+            BindingScreenRoot,
+        ))
+        .with_children(|parent| {
+            parent
+                .spawn((
+                    NodeBundle {
+                        style: Style {
+                            flex_direction: FlexDirection::Column,
+                            row_gap: Val::Px(5.0),
+                            padding: UiRect::all(Val::Px(20.0)),
+                            ..default()
+                        },
+                        ..default()
+                    },
+                    ThemeColor::Board,
+                ))
+                .with_children(|parent| {
+                    parent.spawn((
+                        TextBundle::from_section(
+                            "Key bindings",
+                            TextStyle {
+                                font: font_spec.family.clone(),
+                                font_size: 20.0,
+                                color: Color::WHITE,
+                            },
+                        ),
+                        ThemeColor::Text,
+                    ));
+                    parent.spawn((
+                        NodeBundle {
+                            style: Style {
+                                flex_direction: FlexDirection::Column,
+                                row_gap: Val::Px(5.0),
+                                ..default()
+                            },
+                            ..default()
+                        },
+                        BindingList,
+                    ));
+                });
+        });
+}
@@ -0,0 +1,187 @@
+use crate::components::{Game, NextGame, RunState};
+use crate::puzzle::PuzzleRequest;
+use crate::saves::{read_replay, read_save, ReplayPlayer};
+use crate::settings::Settings;
+use crate::theme::{theme_names, Themes};
+use crate::utility::{new_game, BoardShift, MoveQueue};
+use bevy::app::AppExit;
+use bevy::prelude::*;
+use clap::{error::ErrorKind, CommandFactory, Parser, ValueEnum};
+use rand::{rngs::StdRng, seq::SliceRandom, SeedableRng};
+use std::{path::PathBuf, time::Duration};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Mode {
+    Classic,
+    Puzzle,
+}
+
+/// The 2048 sliding tile game.
+#[derive(Debug, Parser)]
+#[command(name = "boxes", version)]
+pub struct Cli {
+    /// Number of cells along each side of the board, the one picked
+    /// in the settings when left out
+    #[arg(long, value_parser = clap::value_parser!(u8).range(3..=8))]
+    pub size: Option<u8>,
+    /// Seed for the spawned tiles, random when left out
+    #[arg(long)]
+    pub seed: Option<u64>,
+    #[arg(long, value_enum, default_value_t = Mode::Classic)]
+    pub mode: Mode,
+    /// Puzzle to start in puzzle mode, counting from 0
+    #[arg(long, default_value_t = 0)]
+    pub puzzle: usize,
+    /// Theme from assets/themes, by file name without .theme.ron,
+    /// the one picked in the settings when left out
+    #[arg(long)]
+    pub theme: Option<String>,
+    /// Window size in logical pixels, as WIDTHxHEIGHT
+    #[arg(long, value_parser = parse_window_size)]
+    pub window_size: Option<(f32, f32)>,
+    /// Replay file to play back
+    #[arg(long, conflicts_with_all = ["load", "size", "seed", "mode"])]
+    pub replay: Option<PathBuf>,
+    /// Saved game to continue
+    #[arg(long, conflicts_with_all = ["size", "seed", "mode"])]
+    pub load: Option<PathBuf>,
+    /// Run without a window, playing the replay or random shifts until the game ends
+    #[arg(long)]
+    pub headless: bool,
+}
+
+fn parse_window_size(value: &str) -> Result<(f32, f32), String> {
+    let (width, height) = value
+        .split_once('x')
+        .ok_or_else(|| format!("expected WIDTHxHEIGHT, got '{value}'"))?;
+    let parse = |side: &str| match side.trim().parse::<f32>() {
+        Ok(pixels) if pixels >= 200.0 => Ok(pixels),
+        Ok(_) => Err(format!("'{side}' is smaller than 200 pixels")),
+        Err(_) => Err(format!("'{side}' is not a number")),
+    };
+    Ok((parse(width)?, parse(height)?))
+}
+
+impl Cli {
+    /// Parses the command line, exiting with a usage error on options that
+    /// don't go together.
+    pub fn parse_checked() -> Cli {
+        let cli = Cli::parse();
+        // The puzzle sets up the board, clap can't tie a conflict to a value
+        if cli.mode == Mode::Puzzle && (cli.size.is_some() || cli.seed.is_some()) {
+            Cli::command()
+                .error(
+                    ErrorKind::ArgumentConflict,
+                    "--size and --seed can't be used with '--mode puzzle'",
+                )
+                .exit();
+        }
+        cli
+    }
+
+    /// A game given on the command line starts right away instead of the menu.
+    /// Puzzles start on their own once the pack loaded.
+    fn starts_game(&self) -> bool {
+        self.headless
+            || self.size.is_some()
+            || self.seed.is_some()
+            || self.load.is_some()
+            || self.replay.is_some()
+    }
+
+    /// Sets up the first game, failing on unknown themes and on save or
+    /// replay files that can't be read.
+    pub fn configure(&self, app: &mut App) -> Result<(), String> {
+        let settings = app.world.resource::<Settings>().clone();
+        app.insert_resource(NextGame {
+            board_size: self.size.unwrap_or(settings.board_size),
+            seed: self.seed,
+            ..settings.next_game()
+        });
+        if let Some(theme) = &self.theme {
+            let names = theme_names();
+            // The built-in colors are there without a file
+            if theme != "default" && !names.contains(theme) {
+                return Err(format!(
+                    "no theme named '{theme}' in assets/themes, expected one of: {}",
+                    names.join(", ")
+                ));
+            }
+        }
+        app.world.resource_mut::<Themes>().active = self.theme.clone().unwrap_or(settings.theme);
+        if self.mode == Mode::Puzzle {
+            app.insert_resource(PuzzleRequest(Some(self.puzzle)));
+        }
+        if let Some(path) = &self.load {
+            app.insert_resource(read_save(path)?);
+        }
+        if let Some(path) = &self.replay {
+            let replay = read_replay(path)?;
+            app.insert_resource(NextGame {
+                one_off: true,
+                ..replay.game.clone()
+            });
+            app.world.resource_mut::<ReplayPlayer>().pending = Some(replay);
+        }
+        if self.starts_game() && self.mode == Mode::Classic {
+            app.add_systems(Startup, new_game);
+        }
+        if self.headless {
+            app.world.resource_mut::<MoveQueue>().snap_animations = true;
+            app.world.resource_mut::<ReplayPlayer>().interval =
+                Timer::new(Duration::from_millis(1), TimerMode::Repeating);
+            app.add_plugins(HeadlessPlugin {
+                autoplay: self.replay.is_none(),
+            });
+        }
+        Ok(())
+    }
+}
+
+pub struct HeadlessPlugin {
+    /// Play random shifts, otherwise only a replay is played.
+    pub autoplay: bool,
+}
+
+impl Plugin for HeadlessPlugin {
+    fn build(&self, app: &mut App) {
+        if self.autoplay {
+            app.add_systems(Update, autoplay.run_if(in_state(RunState::Playing)));
+        } else {
+            app.add_systems(Update, exit_after_replay.run_if(in_state(RunState::Playing)));
+        }
+        app.add_systems(OnEnter(RunState::GameOver), exit_on_game_over);
+    }
+}
+
+// Seeded from the game, so runs with the same --seed play the same shifts.
+// Kept apart from GameRng, whose draws have to match the replay's.
+fn autoplay(game: Res<Game>, mut move_queue: ResMut<MoveQueue>, mut rng: Local<Option<StdRng>>) {
+    let rng = rng.get_or_insert_with(|| StdRng::seed_from_u64(game.seed));
+    if move_queue.moves.is_empty() {
+        if let Some(board_shift) = BoardShift::ALL.choose(rng) {
+            move_queue.moves.push_back(*board_shift);
+        }
+    }
+}
+
+fn exit_after_replay(
+    game: Res<Game>,
+    player: Res<ReplayPlayer>,
+    move_queue: Res<MoveQueue>,
+    mut exit: EventWriter<AppExit>,
+) {
+    let done = player.pending.is_none() && player.shifts.is_empty() && move_queue.moves.is_empty();
+    if done && game.moves > 0 {
+        info!("Replay finished with {} points after {} moves", game.score, game.moves);
+        exit.send(AppExit);
+    }
+}
+
+fn exit_on_game_over(game: Res<Game>, mut exit: EventWriter<AppExit>) {
+    info!(
+        "Game over: {} points after {} moves ({:?}, seed {})",
+        game.score, game.moves, game.mode, game.seed
+    );
+    exit.send(AppExit);
+}
@@ -1,5 +1,12 @@
+use crate::utility::BoardShift;
 use bevy::prelude::*;
+use bevy::time::Stopwatch;
+use rand::{rngs::StdRng, SeedableRng};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
 
+/// Tile size and spacing in a window of the default size,
+/// `Board` scales both to fit the window.
 pub const TILE_SIZE: f32 = 100.0;
 pub const TILE_SPACER: f32 = 10.0;
 
@@ -7,27 +14,42 @@ pub const TILE_SPACER: f32 = 10.0;
 pub struct Board {
     pub size: u8,
     pub physical_size: f32,
+    pub tile_size: f32,
 }
 impl Board {
-    pub fn new(size: u8) -> Self {
-        let physical_size = f32::from(size) * TILE_SIZE + f32::from(size + 1) * TILE_SPACER;
+    /// A board of `size` cells along each side, `physical_size` pixels wide.
+    pub fn new(size: u8, physical_size: f32) -> Self {
+        let tile_size = physical_size * TILE_SIZE
+            / (f32::from(size) * TILE_SIZE + f32::from(size + 1) * TILE_SPACER);
 
         Board {
             size,
             physical_size,
+            tile_size,
         }
     }
 
+    pub fn spacer(&self) -> f32 {
+        self.tile_size * TILE_SPACER / TILE_SIZE
+    }
+
     pub fn cell_position_to_physical(&self, pos: u8) -> f32 {
-        let offset = 0.5 * (-self.physical_size + TILE_SIZE);
-        offset + f32::from(pos) * TILE_SIZE + f32::from(pos + 1) * TILE_SPACER
+        let offset = 0.5 * (-self.physical_size + self.tile_size);
+        offset + f32::from(pos) * self.tile_size + f32::from(pos + 1) * self.spacer()
     }
 }
 
+/// An empty cell under the tiles.
+#[derive(Component)]
+pub struct Cell {
+    pub x: u8,
+    pub y: u8,
+}
+
 #[derive(
     Component, Debug,
     PartialEq, Clone, 
-    Copy,
+    Copy, Serialize, Deserialize,
 )]
 pub struct Points {
     pub value: u32,
@@ -37,11 +59,90 @@ pub struct Points {
 pub struct Game {
     pub score: u32,
     pub best_score: u32,
+    /// Best score when the game started, to tell whether it set a new one.
+    pub best_at_start: u32,
+    pub mode: GameMode,
+    pub seed: u64,
+    pub moves: u32,
+    pub undos: u32,
+    pub hints: u32,
+    /// Set once the finished game is logged, ending it again after an undo
+    /// rewrites its record instead of adding one.
+    pub recorded: bool,
+    pub clock: Stopwatch,
+    /// How the game was set up, with the seed it ended up using.
+    pub setup: NextGame,
+    /// Every shift since the setup, enough to replay the game.
+    pub shifts: Vec<BoardShift>,
+}
+
+#[derive(
+    Default, Debug, PartialEq, 
+    Clone, Copy, Eq, Hash,
+    Serialize, Deserialize,
+)]
+pub enum GameMode {
+    #[default]
+    Classic,
+    Puzzle,
+}
+
+/// What the next new game is set up with, saved games are stored in this shape too.
+#[derive(Resource, Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NextGame {
+    pub board_size: u8,
+    pub mode: GameMode,
+    /// A random seed is picked when there is none.
+    pub seed: Option<u64>,
+    /// Starting tiles, two random ones when empty.
+    pub tiles: Vec<(Position, Points)>,
+    pub spawns: VecDeque<(Position, Points)>,
+    /// Chance of a spawned tile being a 4 rather than a 2.
+    pub four_chance: f32,
+    pub score: u32,
+    pub moves: u32,
+    /// Only used for one game, restarting afterwards
+    /// begins a fresh game of the same size.
+    pub one_off: bool,
+}
+
+impl Default for NextGame {
+    fn default() -> Self {
+        NextGame {
+            board_size: 4,
+            mode: GameMode::Classic,
+            seed: None,
+            tiles: Vec::new(),
+            spawns: VecDeque::new(),
+            four_chance: 0.0,
+            score: 0,
+            moves: 0,
+            one_off: false,
+        }
+    }
+}
+
+// Tiles spawned after a move are taken from here first,
+// falling back to a random free cell.
+#[derive(Default, Resource, Clone)]
+pub struct SpawnQueue(pub VecDeque<(Position, Points)>);
+
+// Every random choice of a game goes through this rng,
+// so a game can be reproduced from its seed.
+#[derive(Resource)]
+pub struct GameRng(pub StdRng);
+
+impl Default for GameRng {
+    fn default() -> Self {
+        GameRng(StdRng::seed_from_u64(0))
+    }
 }
 #[derive(
     Component, Debug,
     PartialEq, Clone, 
-    Copy, Hash, Eq
+    Copy, Hash, Eq,
+    Serialize, Deserialize,
 )]
 pub struct Position {
     pub x: u8,
@@ -72,6 +173,8 @@ impl FromWorld for FontSpec {
 )]
 pub enum RunState {
     #[default]
+    Menu,
     Playing,
+    Paused,
     GameOver,
 }
\ No newline at end of file
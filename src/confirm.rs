@@ -0,0 +1,255 @@
+use crate::bindings::keyboard_actions;
+use crate::components::{FontSpec, Game, NextGame, RunState};
+use crate::menu::{toggle_menu, Menu};
+use crate::puzzle::{ActivePuzzle, PuzzleRequest};
+use crate::saves::{start_replay, Replay, ReplayPlayer};
+use crate::settings::Settings;
+use crate::styles::{overlay_root, small_button_style, DIMMED};
+use crate::theme::{Theme, ThemeColor};
+use crate::timeline::Timeline;
+use crate::ui::spawn_button;
+use crate::utility::NewGameEvent;
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+
+/// Ways of leaving a game before it is over.
+#[derive(Debug, Clone)]
+pub enum Abandon {
+    EndGame,
+    Restart,
+    /// Starts this game in place of the one going.
+    Start(NextGame),
+    /// Starts this puzzle of the pack.
+    Puzzle(usize),
+    /// Plays this replay back.
+    Replay(Replay),
+}
+
+/// Asks to end or replace the game, which is confirmed first
+/// while a game is going unless the settings turned that off.
+#[derive(Event, Debug, Clone)]
+pub struct AbandonRequest(pub Abandon);
+
+/// Request waiting for a yes or no.
+#[derive(Resource, Default)]
+pub struct Confirmation(pub Option<Abandon>);
+
+pub fn no_confirmation(confirmation: Res<Confirmation>) -> bool {
+    confirmation.0.is_none()
+}
+
+#[derive(Component)]
+struct ConfirmRoot;
+
+#[derive(Component)]
+struct ConfirmText;
+
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+enum ConfirmButton {
+    Yes,
+    No,
+}
+
+pub struct ConfirmPlugin;
+impl Plugin for ConfirmPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Confirmation>()
+            .add_event::<AbandonRequest>()
+            .add_systems(Startup, setup_confirm_dialog)
+            // Takes the keys before the bindings and the menu see them
+            .add_systems(
+                PreUpdate,
+                confirm_keys
+                    .run_if(not(no_confirmation))
+                    .after(bevy::input::InputSystem)
+                    .before(keyboard_actions)
+                    .before(toggle_menu),
+            )
+            .add_systems(
+                Update,
+                (abandon_requests, confirm_buttons, render_confirm_dialog).chain(),
+            );
+    }
+}
+
+/// What carrying out a request touches.
+#[derive(SystemParam)]
+struct Abandoner<'w> {
+    next_state: ResMut<'w, NextState<RunState>>,
+    next_game: ResMut<'w, NextGame>,
+    puzzle_request: ResMut<'w, PuzzleRequest>,
+    active_puzzle: ResMut<'w, ActivePuzzle>,
+    player: ResMut<'w, ReplayPlayer>,
+    new_game_writer: EventWriter<'w, NewGameEvent>,
+}
+
+impl Abandoner<'_> {
+    fn abandon(&mut self, request: Abandon) {
+        match request {
+            Abandon::EndGame => self.next_state.set(RunState::GameOver),
+            Abandon::Restart => self.new_game_writer.send(NewGameEvent),
+            Abandon::Start(next_game) => {
+                *self.next_game = next_game;
+                self.active_puzzle.index = None;
+                self.new_game_writer.send(NewGameEvent);
+            }
+            Abandon::Puzzle(index) => self.puzzle_request.0 = Some(index),
+            Abandon::Replay(replay) => {
+                start_replay(replay, &mut self.next_game, &mut self.player, &mut self.new_game_writer)
+            }
+        }
+    }
+}
+
+fn abandon_requests(
+    mut request_reader: EventReader<AbandonRequest>,
+    run_state: Res<State<RunState>>,
+    game: Res<Game>,
+    menu: Res<Menu>,
+    timeline: Res<Timeline>,
+    settings: Res<Settings>,
+    mut confirmation: ResMut<Confirmation>,
+    mut abandoner: Abandoner,
+) {
+    for AbandonRequest(request) in request_reader.iter() {
+        // The scrubbed score isn't the game's, it ends back at the live board
+        if matches!(request, Abandon::EndGame) && !timeline.is_live() {
+            continue;
+        }
+        // A menu opened during a game can still go back to it
+        let going = match run_state.get() {
+            RunState::Playing | RunState::Paused => true,
+            RunState::Menu => menu.resume,
+            RunState::GameOver => false,
+        };
+        let in_progress = going && game.moves > 0;
+        if settings.confirm_abandon && in_progress {
+            confirmation.0 = Some(request.clone());
+        } else {
+            abandoner.abandon(request.clone());
+        }
+    }
+}
+
+// Enter or Y confirms, Escape or N cancels.
+fn confirm_keys(
+    mut input: ResMut<Input<KeyCode>>,
+    mut confirmation: ResMut<Confirmation>,
+    mut abandoner: Abandoner,
+) {
+    let yes = input.clear_just_pressed(KeyCode::Return) | input.clear_just_pressed(KeyCode::Y);
+    let no = input.clear_just_pressed(KeyCode::Escape) | input.clear_just_pressed(KeyCode::N);
+    if yes {
+        if let Some(request) = confirmation.0.take() {
+            abandoner.abandon(request);
+        }
+    } else if no {
+        confirmation.0 = None;
+    }
+}
+
+fn confirm_buttons(
+    mut interaction_query: Query<(&Interaction, &mut BackgroundColor, &ConfirmButton), Changed<Interaction>>,
+    theme: Res<Theme>,
+    mut confirmation: ResMut<Confirmation>,
+    mut abandoner: Abandoner,
+) {
+    for (interaction, mut color, button) in interaction_query.iter_mut() {
+        *color = theme.button_color(*interaction);
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+        let request = confirmation.0.take();
+        if let (ConfirmButton::Yes, Some(request)) = (button, request) {
+            abandoner.abandon(request);
+        }
+    }
+}
+
+fn render_confirm_dialog(
+    confirmation: Res<Confirmation>,
+    mut root_query: Query<&mut Style, With<ConfirmRoot>>,
+    mut text_query: Query<&mut Text, With<ConfirmText>>,
+) {
+    if !confirmation.is_changed() {
+        return;
+    }
+    let question = match &confirmation.0 {
+        Some(Abandon::EndGame) => "End this game?",
+        Some(Abandon::Restart) => "Restart? This game will be lost.",
+        Some(Abandon::Start(_) | Abandon::Puzzle(_) | Abandon::Replay(_)) => "Start a new game? This game will be lost.",
+        None => "",
+    };
+    text_query.single_mut().sections[0].value = question.to_string();
+    root_query.single_mut().display = if confirmation.0.is_some() {
+        Display::Flex
+    } else {
+        Display::None
+    };
+}
+
+fn setup_confirm_dialog(mut commands: Commands, font_spec: Res<FontSpec>) {
+    commands
+        .spawn((
+            // Above the pause menu it can be opened from
+            NodeBundle {
+                background_color: BackgroundColor(DIMMED),
+                ..overlay_root(7)
+            },
+            ConfirmRoot,
+        ))
+        .with_children(|parent| {
+            parent
+                .spawn((
+                    NodeBundle {
+                        style: Style {
+                            flex_direction: FlexDirection::Column,
+                            align_items: AlignItems::Center,
+                            row_gap: Val::Px(10.0),
+                            padding: UiRect::all(Val::Px(20.0)),
+                            ..default()
+                        },
+                        ..default()
+                    },
+                    ThemeColor::Board,
+                ))
+                .with_children(|parent| {
+                    parent.spawn((
+                        TextBundle::from_section(
+                            "",
+                            TextStyle {
+                                font: font_spec.family.clone(),
+                                font_size: 20.0,
+                                color: Color::WHITE,
+                            },
+                        ),
+                        ThemeColor::Text,
+                        ConfirmText,
+                    ));
+                    parent
+                        .spawn(NodeBundle {
+                            style: Style {
+                                column_gap: Val::Px(10.0),
+                                ..default()
+                            },
+                            ..default()
+                        })
+                        .with_children(|parent| {
+                            spawn_button(
+                                parent,
+                                &font_spec,
+                                "Yes (Enter)",
+                                ConfirmButton::Yes,
+                                small_button_style(),
+                            );
+                            spawn_button(
+                                parent,
+                                &font_spec,
+                                "No (Esc)",
+                                ConfirmButton::No,
+                                small_button_style(),
+                            );
+                        });
+                });
+        });
+}
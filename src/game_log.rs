@@ -0,0 +1,120 @@
+use crate::components::{Board, Game, GameMode, Points};
+use crate::storage::data_dir;
+use crate::timeline::Timeline;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::{self, OpenOptions},
+    io::{self, BufRead, BufReader, Write},
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+const LOG_FILE: &str = "games.jsonl";
+
+/// One finished game, stored as a single line of the game log.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GameRecord {
+    /// Seconds since the unix epoch when the game ended.
+    pub timestamp: u64,
+    pub mode: GameMode,
+    pub board_size: u8,
+    pub seed: u64,
+    pub score: u32,
+    pub highest_tile: u32,
+    pub moves: u32,
+    pub duration_secs: f32,
+    pub undos: u32,
+    pub hints: u32,
+    /// Score after each move, empty for games logged before it was kept.
+    #[serde(default)]
+    pub scores: Vec<u32>,
+}
+
+pub fn log_path() -> PathBuf {
+    data_dir().join(LOG_FILE)
+}
+
+pub fn append_record(path: &Path, record: &GameRecord) -> io::Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let line = serde_json::to_string(record)?;
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    writeln!(file, "{line}")
+}
+
+/// Reads every record of the log, skipping lines that can't be parsed.
+/// A missing log is an empty history.
+pub fn read_records(path: &Path) -> io::Result<Vec<GameRecord>> {
+    let file = match fs::File::open(path) {
+        Ok(file) => file,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(err) => return Err(err),
+    };
+    let mut records = Vec::new();
+    for (index, line) in BufReader::new(file).lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        match serde_json::from_str(&line) {
+            Ok(record) => records.push(record),
+            Err(err) => warn!("Skipping line {} of {}: {}", index + 1, path.display(), err),
+        }
+    }
+    Ok(records)
+}
+
+/// Swaps the last record of the log for this one.
+pub fn replace_last_record(path: &Path, record: &GameRecord) -> io::Result<()> {
+    let contents = match fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => String::new(),
+        Err(err) => return Err(err),
+    };
+    let mut lines: Vec<&str> = contents.lines().filter(|line| !line.trim().is_empty()).collect();
+    lines.pop();
+    let line = serde_json::to_string(record)?;
+    lines.push(&line);
+    fs::write(path, lines.join("\n") + "\n")
+}
+
+pub fn record_finished_game(
+    mut game: ResMut<Game>,
+    timeline: Res<Timeline>,
+    tiles: Query<&Points>,
+    query_board: Query<&Board>,
+) {
+    // Nothing was played, so there is nothing worth keeping.
+    if game.moves == 0 {
+        return;
+    }
+    let board = query_board.single();
+    let record = GameRecord {
+        timestamp: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_secs())
+            .unwrap_or_default(),
+        mode: game.mode,
+        board_size: board.size,
+        seed: game.seed,
+        score: game.score,
+        highest_tile: tiles.iter().map(|points| points.value).max().unwrap_or(0),
+        moves: game.moves,
+        duration_secs: game.clock.elapsed_secs(),
+        undos: game.undos,
+        hints: game.hints,
+        scores: timeline.snapshots.iter().map(|snapshot| snapshot.score).collect(),
+    };
+    // A game ending again after an undo keeps a single record, the last ending's
+    let written = if game.recorded {
+        replace_last_record(&log_path(), &record)
+    } else {
+        append_record(&log_path(), &record)
+    };
+    match written {
+        Ok(()) => game.recorded = true,
+        Err(err) => error!("Failed to write game log: {}", err),
+    }
+}
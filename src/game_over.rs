@@ -0,0 +1,269 @@
+use crate::components::{FontSpec, Game, NextGame, Points, RunState};
+use crate::leaderboard::{submit_score, Leaderboard, PendingScore};
+use crate::saves::{start_replay, LastReplay, ReplayPlayer};
+use crate::settings::Settings;
+use crate::styles::{overlay_root, small_button_style, DIMMED};
+use crate::theme::{Theme, ThemeColor};
+use crate::timeline::Timeline;
+use crate::ui::{spawn_button, ButtonAction};
+use crate::utility::NewGameEvent;
+use bevy::prelude::*;
+
+#[derive(Component)]
+struct GameOverRoot;
+
+#[derive(Component)]
+struct GameOverStats;
+
+#[derive(Component)]
+struct GameOverActions;
+
+#[derive(Component)]
+struct NamePrompt;
+
+#[derive(Component)]
+struct NamePromptText;
+
+/// Buttons only the game over screen has, the others are `ButtonAction`s.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+enum GameOverButton {
+    ViewReplay,
+    SaveScore,
+}
+
+pub struct GameOverPlugin;
+impl Plugin for GameOverPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Startup, setup_game_over)
+            .add_systems(
+                Update,
+                (game_over_buttons, render_game_over, render_name_prompt).chain(),
+            );
+    }
+}
+
+fn format_duration(secs: f32) -> String {
+    let secs = secs as u32;
+    format!("{}:{:02}", secs / 60, secs % 60)
+}
+
+fn game_over_buttons(
+    mut interaction_query: Query<
+        (&Interaction, &mut BackgroundColor, &GameOverButton),
+        Changed<Interaction>,
+    >,
+    theme: Res<Theme>,
+    last_replay: Res<LastReplay>,
+    mut next_game: ResMut<NextGame>,
+    mut player: ResMut<ReplayPlayer>,
+    mut leaderboard: ResMut<Leaderboard>,
+    mut pending: ResMut<PendingScore>,
+    mut new_game_writer: EventWriter<NewGameEvent>,
+) {
+    for (interaction, mut color, button) in interaction_query.iter_mut() {
+        *color = theme.button_color(*interaction);
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+        match button {
+            // Played back without being logged or ranked again
+            GameOverButton::ViewReplay => {
+                if let Some(replay) = last_replay.replay.clone() {
+                    start_replay(replay, &mut next_game, &mut player, &mut new_game_writer);
+                }
+            }
+            GameOverButton::SaveScore => submit_score(&mut leaderboard, &mut pending),
+        }
+    }
+}
+
+fn render_game_over(
+    mut commands: Commands,
+    run_state: Res<State<RunState>>,
+    game: Res<Game>,
+    timeline: Res<Timeline>,
+    last_replay: Res<LastReplay>,
+    player: Res<ReplayPlayer>,
+    settings: Res<Settings>,
+    tiles: Query<&Points>,
+    mut root_query: Query<&mut Style, With<GameOverRoot>>,
+    mut stats_query: Query<&mut Text, With<GameOverStats>>,
+    actions_query: Query<Entity, With<GameOverActions>>,
+    font_spec: Res<FontSpec>,
+) {
+    if !run_state.is_changed() && !last_replay.is_changed() {
+        return;
+    }
+    let game_over = *run_state.get() == RunState::GameOver;
+    root_query.single_mut().display = if game_over {
+        Display::Flex
+    } else {
+        Display::None
+    };
+    if !game_over {
+        return;
+    }
+
+    let highest_tile = tiles.iter().map(|points| points.value).max().unwrap_or(0);
+    let best = if game.score > game.best_at_start {
+        "\nNew best score!"
+    } else {
+        ""
+    };
+    stats_query.single_mut().sections[0].value = format!(
+        "Score: {}\nHighest tile: {}\nMoves: {}\nTime: {}{}",
+        game.score,
+        highest_tile,
+        game.moves,
+        format_duration(game.clock.elapsed_secs()),
+        best,
+    );
+
+    let actions = actions_query.single();
+    commands.entity(actions).despawn_descendants();
+    commands.entity(actions).with_children(|parent| {
+        spawn_button(
+            parent,
+            &font_spec,
+            ButtonAction::NewGame.label(),
+            ButtonAction::NewGame,
+            small_button_style(),
+        );
+        // Undoing goes back to playing
+        if timeline.can_undo() && settings.can_undo(game.undos) && !player.playing {
+            spawn_button(
+                parent,
+                &font_spec,
+                ButtonAction::Undo.label(),
+                ButtonAction::Undo,
+                small_button_style(),
+            );
+        }
+        if last_replay.replay.is_some() {
+            spawn_button(
+                parent,
+                &font_spec,
+                "View replay",
+                GameOverButton::ViewReplay,
+                small_button_style(),
+            );
+        }
+    });
+}
+
+fn render_name_prompt(
+    pending: Res<PendingScore>,
+    mut prompt_query: Query<&mut Style, With<NamePrompt>>,
+    mut text_query: Query<&mut Text, With<NamePromptText>>,
+) {
+    if !pending.is_changed() {
+        return;
+    }
+    prompt_query.single_mut().display = if pending.entry.is_some() {
+        Display::Flex
+    } else {
+        Display::None
+    };
+    text_query.single_mut().sections[0].value = format!(
+        "#{} on the leaderboard! Name: {}_",
+        pending.rank + 1,
+        pending.name
+    );
+}
+
+fn setup_game_over(mut commands: Commands, font_spec: Res<FontSpec>) {
+    commands
+        .spawn((
+            NodeBundle {
+                background_color: BackgroundColor(DIMMED),
+                ..overlay_root(5)
+            },
+            GameOverRoot,
+        ))
+        .with_children(|parent| {
+            parent
+                .spawn((
+                    NodeBundle {
+                        style: Style {
+                            flex_direction: FlexDirection::Column,
+                            align_items: AlignItems::Center,
+                            row_gap: Val::Px(10.0),
+                            padding: UiRect::all(Val::Px(20.0)),
+                            ..default()
+                        },
+                        ..default()
+                    },
+                    ThemeColor::Board,
+                ))
+                .with_children(|parent| {
+                    parent.spawn((
+                        TextBundle::from_section(
+                            "Game over",
+                            TextStyle {
+                                font: font_spec.family.clone(),
+                                font_size: 30.0,
+                                color: Color::WHITE,
+                            },
+                        ),
+                        ThemeColor::Text,
+                    ));
+                    parent.spawn((
+                        TextBundle::from_section(
+                            "",
+                            TextStyle {
+                                font: font_spec.family.clone(),
+                                font_size: 20.0,
+                                color: Color::WHITE,
+                            },
+                        )
+                        .with_text_alignment(TextAlignment::Center),
+                        ThemeColor::Text,
+                        GameOverStats,
+                    ));
+                    parent
+                        .spawn((
+                            NodeBundle {
+                                style: Style {
+                                    display: Display::None,
+                                    align_items: AlignItems::Center,
+                                    column_gap: Val::Px(10.0),
+                                    ..default()
+                                },
+                                ..default()
+                            },
+                            NamePrompt,
+                        ))
+                        .with_children(|parent| {
+                            parent.spawn((
+                                TextBundle::from_section(
+                                    "",
+                                    TextStyle {
+                                        font: font_spec.family.clone(),
+                                        font_size: 20.0,
+                                        color: Color::WHITE,
+                                    },
+                                ),
+                                ThemeColor::Text,
+                                NamePromptText,
+                            ));
+                            spawn_button(
+                                parent,
+                                &font_spec,
+                                "Save",
+                                GameOverButton::SaveScore,
+                                small_button_style(),
+                            );
+                        });
+                    parent.spawn((
+                        NodeBundle {
+                            style: Style {
+                                column_gap: Val::Px(10.0),
+                                ..default()
+                            },
+                            ..default()
+                        },
+                        GameOverActions,
+                    ));
+                });
+        });
+}
@@ -0,0 +1,183 @@
+use crate::bindings::{screen_closed, Action, ActionEvent};
+use bevy::input::InputSystem;
+use bevy::prelude::*;
+use bevy::utils::HashSet;
+
+// Both systems only read the `Input` and `Axis` resources filled in by bevy from
+// `GamepadEvent`s, so sending those events drives them without a real gamepad.
+
+const BUTTON_ACTIONS: [(GamepadButtonType, Action); 8] = [
+    (GamepadButtonType::DPadLeft, Action::ShiftLeft),
+    (GamepadButtonType::DPadRight, Action::ShiftRight),
+    (GamepadButtonType::DPadUp, Action::ShiftUp),
+    (GamepadButtonType::DPadDown, Action::ShiftDown),
+    (GamepadButtonType::South, Action::Confirm),
+    (GamepadButtonType::East, Action::Undo),
+    (GamepadButtonType::North, Action::Restart),
+    (GamepadButtonType::Start, Action::Pause),
+];
+
+#[derive(Resource, Debug, Clone, Copy)]
+pub struct StickSettings {
+    /// The stick has to come back within this distance of the center
+    /// before it can shift again.
+    pub dead_zone: f32,
+    /// How far the stick has to be pushed along an axis to shift.
+    pub threshold: f32,
+}
+
+impl Default for StickSettings {
+    fn default() -> Self {
+        StickSettings {
+            dead_zone: 0.3,
+            threshold: 0.7,
+        }
+    }
+}
+
+pub fn flick_direction(x: f32, y: f32, threshold: f32) -> Option<Action> {
+    if x.abs().max(y.abs()) < threshold {
+        return None;
+    }
+    let action = if x.abs() > y.abs() {
+        if x > 0.0 {
+            Action::ShiftRight
+        } else {
+            Action::ShiftLeft
+        }
+    } else if y > 0.0 {
+        Action::ShiftUp
+    } else {
+        Action::ShiftDown
+    };
+    Some(action)
+}
+
+pub struct GamepadPlugin;
+impl Plugin for GamepadPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<StickSettings>().add_systems(
+            PreUpdate,
+            (gamepad_buttons, gamepad_stick)
+                .run_if(screen_closed)
+                .after(InputSystem),
+        );
+    }
+}
+
+fn gamepad_buttons(
+    gamepads: Res<Gamepads>,
+    buttons: Res<Input<GamepadButton>>,
+    mut action_writer: EventWriter<ActionEvent>,
+) {
+    for gamepad in gamepads.iter() {
+        for (button_type, action) in BUTTON_ACTIONS {
+            if buttons.just_pressed(GamepadButton::new(gamepad, button_type)) {
+                action_writer.send(ActionEvent(action));
+            }
+        }
+    }
+}
+
+fn gamepad_stick(
+    gamepads: Res<Gamepads>,
+    axes: Res<Axis<GamepadAxis>>,
+    settings: Res<StickSettings>,
+    mut flicked: Local<HashSet<Gamepad>>,
+    mut action_writer: EventWriter<ActionEvent>,
+) {
+    flicked.retain(|gamepad| gamepads.contains(*gamepad));
+    for gamepad in gamepads.iter() {
+        let x = axes
+            .get(GamepadAxis::new(gamepad, GamepadAxisType::LeftStickX))
+            .unwrap_or(0.0);
+        let y = axes
+            .get(GamepadAxis::new(gamepad, GamepadAxisType::LeftStickY))
+            .unwrap_or(0.0);
+
+        if flicked.contains(&gamepad) {
+            if x.abs().max(y.abs()) < settings.dead_zone {
+                flicked.remove(&gamepad);
+            }
+        } else if let Some(action) = flick_direction(x, y, settings.threshold) {
+            flicked.insert(gamepad);
+            action_writer.send(ActionEvent(action));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bindings::BindingScreen;
+    use bevy::input::gamepad::{
+        GamepadAxisChangedEvent, GamepadButtonChangedEvent, GamepadConnection,
+        GamepadConnectionEvent, GamepadEvent, GamepadInfo,
+    };
+    use bevy::input::InputPlugin;
+
+    const GAMEPAD: Gamepad = Gamepad { id: 0 };
+
+    fn app() -> App {
+        let mut app = App::new();
+        app.add_plugins((MinimalPlugins, InputPlugin, GamepadPlugin))
+            .init_resource::<BindingScreen>()
+            .add_event::<ActionEvent>();
+        let info = GamepadInfo {
+            name: "Test pad".to_string(),
+        };
+        send(&mut app, vec![GamepadEvent::Connection(GamepadConnectionEvent::new(
+            GAMEPAD,
+            GamepadConnection::Connected(info),
+        ))]);
+        app
+    }
+
+    // Sends the events as gilrs would and returns the actions of the next frame.
+    fn send(app: &mut App, events: Vec<GamepadEvent>) -> Vec<Action> {
+        let mut reader = app.world.resource::<Events<ActionEvent>>().get_reader_current();
+        let mut gamepad_events = app.world.resource_mut::<Events<GamepadEvent>>();
+        for event in events {
+            gamepad_events.send(event);
+        }
+        app.update();
+        let actions = app.world.resource::<Events<ActionEvent>>();
+        reader.iter(actions).map(|ActionEvent(action)| *action).collect()
+    }
+
+    fn button(button_type: GamepadButtonType, value: f32) -> GamepadEvent {
+        GamepadEvent::Button(GamepadButtonChangedEvent::new(GAMEPAD, button_type, value))
+    }
+
+    fn stick_x(value: f32) -> GamepadEvent {
+        GamepadEvent::Axis(GamepadAxisChangedEvent::new(GAMEPAD, GamepadAxisType::LeftStickX, value))
+    }
+
+    #[test]
+    fn buttons_send_their_actions_once() {
+        let mut app = app();
+        assert_eq!(send(&mut app, vec![button(GamepadButtonType::DPadLeft, 1.0)]), [Action::ShiftLeft]);
+        // Held down, it doesn't repeat
+        assert!(send(&mut app, vec![]).is_empty());
+        assert!(send(&mut app, vec![button(GamepadButtonType::DPadLeft, 0.0)]).is_empty());
+        assert_eq!(send(&mut app, vec![button(GamepadButtonType::South, 1.0)]), [Action::Confirm]);
+        assert_eq!(send(&mut app, vec![button(GamepadButtonType::East, 1.0)]), [Action::Undo]);
+    }
+
+    #[test]
+    fn stick_flicks_once_until_back_in_the_dead_zone() {
+        let mut app = app();
+        assert_eq!(send(&mut app, vec![stick_x(0.9)]), [Action::ShiftRight]);
+        assert!(send(&mut app, vec![stick_x(0.5)]).is_empty());
+        assert!(send(&mut app, vec![stick_x(-0.9)]).is_empty());
+        assert!(send(&mut app, vec![stick_x(0.0)]).is_empty());
+        assert_eq!(send(&mut app, vec![stick_x(-0.9)]), [Action::ShiftLeft]);
+    }
+
+    #[test]
+    fn binding_screen_takes_the_gamepad() {
+        let mut app = app();
+        app.world.resource_mut::<BindingScreen>().open = true;
+        assert!(send(&mut app, vec![button(GamepadButtonType::DPadUp, 1.0)]).is_empty());
+    }
+}
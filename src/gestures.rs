@@ -0,0 +1,142 @@
+use crate::bindings::{screen_closed, Action, ActionEvent};
+use crate::components::{Board, RunState};
+use bevy::prelude::*;
+use bevy::utils::HashSet;
+use bevy::window::PrimaryWindow;
+
+#[derive(Resource, Debug, Clone, Copy)]
+pub struct SwipeSettings {
+    /// Shortest drag in logical pixels that counts as a swipe.
+    pub min_distance: f32,
+    /// How far in degrees a swipe may stray from a straight line.
+    pub angle_tolerance: f32,
+}
+
+impl Default for SwipeSettings {
+    fn default() -> Self {
+        SwipeSettings {
+            min_distance: 30.0,
+            angle_tolerance: 30.0,
+        }
+    }
+}
+
+/// Turns a drag in window coordinates, where y grows downwards, into a shift.
+pub fn swipe_action(delta: Vec2, settings: &SwipeSettings) -> Option<Action> {
+    if delta.length() < settings.min_distance {
+        return None;
+    }
+    let (major, minor) = if delta.x.abs() >= delta.y.abs() {
+        (delta.x.abs(), delta.y.abs())
+    } else {
+        (delta.y.abs(), delta.x.abs())
+    };
+    if minor.atan2(major).to_degrees() > settings.angle_tolerance {
+        return None;
+    }
+    let action = if delta.x.abs() >= delta.y.abs() {
+        if delta.x > 0.0 {
+            Action::ShiftRight
+        } else {
+            Action::ShiftLeft
+        }
+    } else if delta.y > 0.0 {
+        Action::ShiftDown
+    } else {
+        Action::ShiftUp
+    };
+    Some(action)
+}
+
+pub struct GesturePlugin;
+impl Plugin for GesturePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<SwipeSettings>()
+            .add_systems(
+                Update,
+                (mouse_swipes, touch_swipes)
+                    .run_if(in_state(RunState::Playing))
+                    .run_if(screen_closed),
+            );
+    }
+}
+
+// A swipe has to start on the board and not on a ui node someone can click.
+fn starts_on_board(
+    position: Vec2,
+    interactions: &Query<&Interaction>,
+    camera_query: &Query<(&Camera, &GlobalTransform)>,
+    board_query: &Query<(&Board, &GlobalTransform)>,
+) -> bool {
+    if interactions
+        .iter()
+        .any(|interaction| *interaction != Interaction::None)
+    {
+        return false;
+    }
+    let Ok((camera, camera_transform)) = camera_query.get_single() else {
+        return false;
+    };
+    let Some(world) = camera.viewport_to_world_2d(camera_transform, position) else {
+        return false;
+    };
+    let Ok((board, board_transform)) = board_query.get_single() else {
+        return false;
+    };
+    let offset = world - board_transform.translation().truncate();
+    offset.abs().max_element() <= 0.5 * board.physical_size
+}
+
+fn mouse_swipes(
+    buttons: Res<Input<MouseButton>>,
+    window_query: Query<&Window, With<PrimaryWindow>>,
+    interactions: Query<&Interaction>,
+    camera_query: Query<(&Camera, &GlobalTransform)>,
+    board_query: Query<(&Board, &GlobalTransform)>,
+    settings: Res<SwipeSettings>,
+    mut drag_start: Local<Option<Vec2>>,
+    mut action_writer: EventWriter<ActionEvent>,
+) {
+    let Ok(window) = window_query.get_single() else {
+        return;
+    };
+    let cursor = window.cursor_position();
+    if buttons.just_pressed(MouseButton::Left) {
+        *drag_start = cursor.filter(|position| {
+            starts_on_board(*position, &interactions, &camera_query, &board_query)
+        });
+    }
+    if buttons.just_released(MouseButton::Left) {
+        if let (Some(start), Some(end)) = (drag_start.take(), cursor) {
+            if let Some(action) = swipe_action(end - start, &settings) {
+                action_writer.send(ActionEvent(action));
+            }
+        }
+    }
+}
+
+fn touch_swipes(
+    touches: Res<Touches>,
+    interactions: Query<&Interaction>,
+    camera_query: Query<(&Camera, &GlobalTransform)>,
+    board_query: Query<(&Board, &GlobalTransform)>,
+    settings: Res<SwipeSettings>,
+    mut swipes: Local<HashSet<u64>>,
+    mut action_writer: EventWriter<ActionEvent>,
+) {
+    for touch in touches.iter_just_pressed() {
+        if starts_on_board(touch.start_position(), &interactions, &camera_query, &board_query) {
+            swipes.insert(touch.id());
+        }
+    }
+    for touch in touches.iter_just_released() {
+        if swipes.remove(&touch.id()) {
+            if let Some(action) = swipe_action(touch.position() - touch.start_position(), &settings) {
+                action_writer.send(ActionEvent(action));
+            }
+        }
+    }
+    for touch in touches.iter_just_canceled() {
+        swipes.remove(&touch.id());
+    }
+}
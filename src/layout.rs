@@ -0,0 +1,114 @@
+use crate::animation::Absorbed;
+use crate::components::{Board, Cell, Points, Position, TileText};
+use crate::skins::TileSkin;
+use crate::utility::tile_font_size;
+use bevy::prelude::*;
+use bevy::window::{PrimaryWindow, WindowResized};
+use bevy_easings::EasingComponent;
+
+/// Window size the ui is laid out for, everything scales from there.
+const REFERENCE_WIDTH: f32 = 1280.0;
+const REFERENCE_HEIGHT: f32 = 720.0;
+/// Share of the reference height the board takes.
+const BOARD_SHARE: f32 = 0.6;
+
+/// How much larger than the reference a window is, keeping the aspect ratio.
+pub fn window_scale(width: f32, height: f32) -> f32 {
+    (width / REFERENCE_WIDTH).min(height / REFERENCE_HEIGHT)
+}
+
+/// Width of the board in pixels, fit to the window.
+#[derive(Resource, Debug, Clone, Copy, PartialEq)]
+pub struct BoardArea(pub f32);
+
+impl BoardArea {
+    pub fn fit(width: f32, height: f32) -> Self {
+        BoardArea(BOARD_SHARE * REFERENCE_HEIGHT * window_scale(width, height))
+    }
+}
+
+// Without a window, as in headless runs, or with a minimized one,
+// the board keeps the reference size.
+impl FromWorld for BoardArea {
+    fn from_world(world: &mut World) -> Self {
+        let mut windows = world.query_filtered::<&Window, With<PrimaryWindow>>();
+        match windows.get_single(world) {
+            Ok(window) if window.width() > 0.0 && window.height() > 0.0 => {
+                BoardArea::fit(window.width(), window.height())
+            }
+            _ => BoardArea::fit(REFERENCE_WIDTH, REFERENCE_HEIGHT),
+        }
+    }
+}
+
+pub struct LayoutPlugin;
+impl Plugin for LayoutPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<BoardArea>()
+            .add_systems(PreUpdate, fit_to_window);
+    }
+}
+
+fn fit_to_window(
+    mut commands: Commands,
+    mut resize_reader: EventReader<WindowResized>,
+    mut fitted: Local<bool>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    mut area: ResMut<BoardArea>,
+    mut ui_scale: ResMut<UiScale>,
+    mut boards: Query<(&mut Board, &mut Sprite), (Without<Cell>, Without<Points>)>,
+    mut cells: Query<(&Cell, &mut Sprite, &mut Transform), (Without<Board>, Without<Points>)>,
+    mut tiles: Query<
+        (Entity, &Position, &Points, &mut Sprite, &mut Transform, &Children),
+        (Without<Board>, Without<Cell>),
+    >,
+    mut texts: Query<&mut Text, With<TileText>>,
+    mut skins: Query<&mut TextureAtlasSprite, With<TileSkin>>,
+    absorbed: Query<Entity, With<Absorbed>>,
+) {
+    // The ui scale still has to be set for the first frame
+    if resize_reader.iter().count() == 0 && *fitted {
+        return;
+    }
+    let Ok(window) = windows.get_single() else {
+        return;
+    };
+    // A minimized window has no size, the layout stays as it was
+    if window.width() <= 0.0 || window.height() <= 0.0 {
+        return;
+    }
+    *fitted = true;
+    ui_scale.scale = f64::from(window_scale(window.width(), window.height()));
+    *area = BoardArea::fit(window.width(), window.height());
+
+    let Ok((mut board, mut sprite)) = boards.get_single_mut() else {
+        return;
+    };
+    *board = Board::new(board.size, area.0);
+    sprite.custom_size = Some(Vec2::splat(board.physical_size));
+    for (cell, mut sprite, mut transform) in cells.iter_mut() {
+        sprite.custom_size = Some(Vec2::splat(board.tile_size));
+        transform.translation.x = board.cell_position_to_physical(cell.x);
+        transform.translation.y = board.cell_position_to_physical(cell.y);
+    }
+    // Running slides would end at the old positions, tiles jump to the new ones
+    for (entity, pos, points, mut sprite, mut transform, children) in tiles.iter_mut() {
+        commands.entity(entity).remove::<EasingComponent<Transform>>();
+        sprite.custom_size = Some(Vec2::splat(board.tile_size));
+        transform.translation.x = board.cell_position_to_physical(pos.x);
+        transform.translation.y = board.cell_position_to_physical(pos.y);
+        for child in children.iter() {
+            if let Ok(mut text) = texts.get_mut(*child) {
+                for section in text.sections.iter_mut() {
+                    section.style.font_size = tile_font_size(points.value, board.tile_size);
+                }
+            }
+            if let Ok(mut skin) = skins.get_mut(*child) {
+                skin.custom_size = Some(Vec2::splat(board.tile_size));
+            }
+        }
+    }
+    for entity in absorbed.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
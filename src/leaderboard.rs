@@ -0,0 +1,230 @@
+use crate::components::{Board, Game, GameMode, Points, RunState};
+use crate::saves::is_playback;
+use crate::storage::{load_json, save_json};
+use crate::utility::NewGameEvent;
+use bevy::prelude::*;
+use bevy::window::ReceivedCharacter;
+use serde::{Deserialize, Serialize};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const LEADERBOARD_FILE: &str = "leaderboard.json";
+/// Entries kept for each board size and mode.
+pub const TOP_ENTRIES: usize = 10;
+const MAX_NAME_LENGTH: usize = 16;
+const DEFAULT_NAME: &str = "Player";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LeaderboardEntry {
+    pub name: String,
+    pub score: u32,
+    pub highest_tile: u32,
+    pub moves: u32,
+    /// Seconds since the unix epoch when the game ended.
+    pub timestamp: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LeaderboardTable {
+    pub board_size: u8,
+    pub mode: GameMode,
+    /// Best score first.
+    pub entries: Vec<LeaderboardEntry>,
+}
+
+/// Best games of each board size and mode.
+#[derive(Resource, Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Leaderboard {
+    pub tables: Vec<LeaderboardTable>,
+    /// Name entered last, offered again for the next high score.
+    pub last_name: String,
+}
+
+impl Leaderboard {
+    pub fn entries(&self, board_size: u8, mode: GameMode) -> &[LeaderboardEntry] {
+        self.tables
+            .iter()
+            .find(|table| table.board_size == board_size && table.mode == mode)
+            .map(|table| table.entries.as_slice())
+            .unwrap_or_default()
+    }
+
+    /// Place a score would take in its table, `None` when it doesn't make the table.
+    pub fn rank(&self, board_size: u8, mode: GameMode, score: u32) -> Option<usize> {
+        if score == 0 {
+            return None;
+        }
+        let entries = self.entries(board_size, mode);
+        let rank = entries.iter().take_while(|entry| entry.score >= score).count();
+        (rank < TOP_ENTRIES).then_some(rank)
+    }
+
+    pub fn insert(&mut self, board_size: u8, mode: GameMode, entry: LeaderboardEntry) {
+        let index = match self
+            .tables
+            .iter()
+            .position(|table| table.board_size == board_size && table.mode == mode)
+        {
+            Some(index) => index,
+            None => {
+                self.tables.push(LeaderboardTable {
+                    board_size,
+                    mode,
+                    entries: Vec::new(),
+                });
+                self.tables.len() - 1
+            }
+        };
+        let entries = &mut self.tables[index].entries;
+        let rank = entries.iter().take_while(|kept| kept.score >= entry.score).count();
+        entries.insert(rank, entry);
+        entries.truncate(TOP_ENTRIES);
+    }
+
+    pub fn remove(&mut self, board_size: u8, mode: GameMode, entry: &LeaderboardEntry) {
+        for table in self.tables.iter_mut() {
+            if table.board_size == board_size && table.mode == mode {
+                table.entries.retain(|kept| kept != entry);
+            }
+        }
+    }
+}
+
+/// A finished game that made the leaderboard, waiting for the player's name.
+#[derive(Resource, Default)]
+pub struct PendingScore {
+    pub entry: Option<(u8, GameMode, LeaderboardEntry)>,
+    pub rank: usize,
+    pub name: String,
+    /// Entry saved for the game being played, taken out again
+    /// when the game ends another time after an undo.
+    pub submitted: Option<(u8, GameMode, LeaderboardEntry)>,
+}
+
+pub fn not_typing(pending: Res<PendingScore>) -> bool {
+    pending.entry.is_none()
+}
+
+/// Puts the pending score in the leaderboard under the entered name.
+pub fn submit_score(leaderboard: &mut Leaderboard, pending: &mut PendingScore) {
+    let Some((board_size, mode, mut entry)) = pending.entry.take() else {
+        return;
+    };
+    let name = pending.name.trim();
+    entry.name = if name.is_empty() { DEFAULT_NAME } else { name }.to_string();
+    leaderboard.last_name = entry.name.clone();
+    leaderboard.insert(board_size, mode, entry.clone());
+    pending.submitted = Some((board_size, mode, entry));
+    if let Err(err) = save_json(LEADERBOARD_FILE, &*leaderboard) {
+        error!("Failed to save the leaderboard: {}", err);
+    }
+}
+
+/// Formats a unix timestamp as a date like 2024-03-09.
+pub fn format_date(timestamp: u64) -> String {
+    // Days to a civil date, after Howard Hinnant's days_from_civil
+    let days = (timestamp / 86_400) as i64 + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 { month_index + 3 } else { month_index - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    format!("{year}-{month:02}-{day:02}")
+}
+
+pub struct LeaderboardPlugin;
+impl Plugin for LeaderboardPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<PendingScore>()
+            .add_systems(Startup, load_leaderboard)
+            // A new game started before the name was entered keeps the score
+            .add_systems(PreUpdate, submit_on_new_game.run_if(on_event::<NewGameEvent>()))
+            .add_systems(OnEnter(RunState::GameOver), check_high_score.run_if(not(is_playback)))
+            // Undoing the last move keeps the game going, its score isn't final
+            .add_systems(OnExit(RunState::GameOver), skip_score)
+            .add_systems(Update, type_name);
+    }
+}
+
+fn load_leaderboard(mut commands: Commands) {
+    commands.insert_resource(load_json::<Leaderboard>(LEADERBOARD_FILE));
+}
+
+fn check_high_score(
+    game: Res<Game>,
+    tiles: Query<&Points>,
+    query_board: Query<&Board>,
+    mut leaderboard: ResMut<Leaderboard>,
+    mut pending: ResMut<PendingScore>,
+) {
+    // The final ending is ranked, not the one before the undo
+    if let Some((board_size, mode, entry)) = pending.submitted.take() {
+        leaderboard.remove(board_size, mode, &entry);
+        if let Err(err) = save_json(LEADERBOARD_FILE, &*leaderboard) {
+            error!("Failed to save the leaderboard: {}", err);
+        }
+    }
+    let board_size = query_board.single().size;
+    let Some(rank) = leaderboard.rank(board_size, game.mode, game.score) else {
+        return;
+    };
+    let entry = LeaderboardEntry {
+        name: String::new(),
+        score: game.score,
+        highest_tile: tiles.iter().map(|points| points.value).max().unwrap_or(0),
+        moves: game.moves,
+        timestamp: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_secs())
+            .unwrap_or_default(),
+    };
+    *pending = PendingScore {
+        entry: Some((board_size, game.mode, entry)),
+        rank,
+        name: leaderboard.last_name.clone(),
+        submitted: None,
+    };
+}
+
+// Key bindings are off while typing, Enter saves and Escape skips the entry.
+// Always reads the characters, so keys pressed before the prompt aren't typed.
+fn type_name(
+    mut char_reader: EventReader<ReceivedCharacter>,
+    input: Res<Input<KeyCode>>,
+    mut pending: ResMut<PendingScore>,
+    mut leaderboard: ResMut<Leaderboard>,
+) {
+    let typed: Vec<char> = char_reader
+        .iter()
+        .map(|event| event.char)
+        .filter(|typed| !typed.is_control())
+        .collect();
+    if pending.entry.is_none() {
+        return;
+    }
+    for typed in typed {
+        if pending.name.chars().count() < MAX_NAME_LENGTH {
+            pending.name.push(typed);
+        }
+    }
+    if input.just_pressed(KeyCode::Back) {
+        pending.name.pop();
+    }
+    if input.just_pressed(KeyCode::Return) {
+        submit_score(&mut leaderboard, &mut pending);
+    } else if input.just_pressed(KeyCode::Escape) {
+        pending.entry = None;
+    }
+}
+
+fn submit_on_new_game(mut leaderboard: ResMut<Leaderboard>, mut pending: ResMut<PendingScore>) {
+    submit_score(&mut leaderboard, &mut pending);
+    pending.submitted = None;
+}
+
+fn skip_score(mut pending: ResMut<PendingScore>) {
+    pending.entry = None;
+}
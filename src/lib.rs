@@ -1,5 +1,24 @@
 pub mod utility;
-pub mod colors;
+pub mod animation;
+pub mod theme;
+pub mod skins;
 pub mod components;
+pub mod confirm;
 pub mod ui;
-pub mod styles;
\ No newline at end of file
+pub mod styles;
+pub mod game_log;
+pub mod game_over;
+pub mod leaderboard;
+pub mod menu;
+pub mod pause;
+pub mod timeline;
+pub mod storage;
+pub mod puzzle;
+pub mod bindings;
+pub mod gamepad;
+pub mod gestures;
+pub mod layout;
+pub mod saves;
+pub mod settings;
+pub mod statistics;
+pub mod cli;
\ No newline at end of file
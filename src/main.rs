@@ -1,47 +1,144 @@
 use bevy::prelude::*;
+use bevy::render::{settings::WgpuSettings, RenderPlugin};
+use bevy::app::ScheduleRunnerPlugin;
+use bevy::asset::ChangeWatcher;
+use bevy::window::ExitCondition;
+use bevy::winit::WinitPlugin;
 use bevy_easings::*;
+use std::time::Duration;
 
 use boxes::utility::{setup, spawn_board, 
     spawn_tiles,render_tile_points, 
     board_shift, render_tiles, 
     new_tile_handler, NewTileEvent, 
-    end_game, game_reset
+    end_game, game_reset, tick_game_clock,
+    start_playing, NewGameEvent,
+    queue_moves, snap_animations, MoveQueue,
+    forget_one_off_game, render_tile_colors,
 };
-use boxes::components::{Game, FontSpec, RunState};
+use boxes::components::{Game, FontSpec, RunState, GameRng, NextGame, SpawnQueue};
+use boxes::animation::AnimationPlugin;
+use boxes::cli::Cli;
+use boxes::confirm::{no_confirmation, ConfirmPlugin};
+use boxes::puzzle::PuzzlePlugin;
+use boxes::bindings::BindingsPlugin;
+use boxes::gamepad::GamepadPlugin;
+use boxes::gestures::GesturePlugin;
+use boxes::layout::LayoutPlugin;
+use boxes::leaderboard::LeaderboardPlugin;
+use boxes::menu::MenuPlugin;
+use boxes::pause::PausePlugin;
+use boxes::game_log::record_finished_game;
+use boxes::game_over::GameOverPlugin;
+use boxes::saves::{is_playback, is_replaying, SavesPlugin};
+use boxes::skins::SkinPlugin;
+use boxes::theme::ThemePlugin;
+use boxes::timeline::{is_live, TimelinePlugin};
 use boxes::ui::GameUiPlugin;
 
 fn main() {
-    App::new()
-        .insert_resource(ClearColor(Color::hex("#1f2638").unwrap()))
-        .add_plugins(DefaultPlugins.set(WindowPlugin {
-            primary_window: Some(Window {
-                title: "2048".to_string(),
-                ..default()
-            }),
+    let cli = Cli::parse_checked();
+    let mut app = App::new();
+
+    if cli.headless {
+        app.add_plugins(
+            DefaultPlugins
+                .set(WindowPlugin {
+                    primary_window: None,
+                    exit_condition: ExitCondition::DontExit,
+                    close_when_requested: false,
+                })
+                .set(RenderPlugin {
+                    wgpu_settings: WgpuSettings {
+                        backends: None,
+                        ..default()
+                    },
+                })
+                .disable::<WinitPlugin>(),
+        )
+        .add_plugins(ScheduleRunnerPlugin::run_loop(Duration::from_millis(1)));
+    } else {
+        let mut window = Window {
+            title: "2048".to_string(),
             ..default()
-        }))
+        };
+        if let Some((width, height)) = cli.window_size {
+            window.resolution = (width, height).into();
+        }
+        app.add_plugins(
+            DefaultPlugins
+                .set(WindowPlugin {
+                    primary_window: Some(window),
+                    ..default()
+                })
+                // Hot reloads edited themes
+                .set(AssetPlugin {
+                    watch_for_changes: ChangeWatcher::with_delay(Duration::from_millis(200)),
+                    ..default()
+                }),
+        );
+    }
+
+    app.add_plugins(LayoutPlugin)
+        .add_plugins(ThemePlugin)
+        .add_plugins(SkinPlugin)
         .add_plugins(GameUiPlugin)
+        .add_plugins(GameOverPlugin)
+        .add_plugins(MenuPlugin)
+        .add_plugins(PausePlugin)
+        .add_plugins(LeaderboardPlugin)
+        .add_plugins(ConfirmPlugin)
+        .add_plugins(TimelinePlugin)
+        .add_plugins(PuzzlePlugin)
+        .add_plugins(BindingsPlugin)
+        .add_plugins(GamepadPlugin)
+        .add_plugins(GesturePlugin)
+        .add_plugins(SavesPlugin)
         .add_plugins(EasingsPlugin)
+        .add_plugins(AnimationPlugin)
         .init_resource::<Game>()
         .init_resource::<FontSpec>()
+        .init_resource::<GameRng>()
+        .init_resource::<NextGame>()
+        .init_resource::<SpawnQueue>()
+        .init_resource::<MoveQueue>()
         // .init_resource::<State<RunState>>()
         .add_event::<NewTileEvent>()
+        .add_event::<NewGameEvent>()
         .add_state::<RunState>()
-        .add_systems(
-            Startup,
-            (setup, spawn_board, apply_deferred)
-            .chain(),
+        // The board is spawned once a game starts from the menu
+        .add_systems(Startup, setup)
+        .add_systems(PreUpdate,
+            (
+                game_reset, spawn_board, apply_deferred,
+                spawn_tiles, forget_one_off_game, start_playing
+            )
+            .chain()
+            .run_if(on_event::<NewGameEvent>()),
         )
         .add_systems(Update, 
             (
-                render_tile_points, board_shift, 
-                render_tiles,  new_tile_handler,
-                end_game
+                render_tile_points,
+                (
+                    queue_moves.run_if(not(is_replaying)).run_if(no_confirmation),
+                    snap_animations, apply_deferred, board_shift
+                )
+                    .chain()
+                    .run_if(is_live),
+                render_tiles,  new_tile_handler.after(board_shift),
+                end_game.run_if(is_live), tick_game_clock
             )
             .run_if(in_state(RunState::Playing))
         )
-        .add_systems(OnEnter(RunState::Playing),
-            (game_reset, spawn_tiles)
-        )
-        .run()
+        .add_systems(PostUpdate, render_tile_colors)
+        .add_systems(
+            OnEnter(RunState::GameOver),
+            record_finished_game.run_if(not(is_playback)),
+        );
+
+    if let Err(err) = cli.configure(&mut app) {
+        eprintln!("error: {err}");
+        std::process::exit(2);
+    }
+    app.run();
 }
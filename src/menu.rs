@@ -0,0 +1,527 @@
+use crate::bindings::{screen_closed, Action, ActionEvent};
+use crate::components::{FontSpec, GameMode, NextGame, RunState};
+use crate::confirm::{no_confirmation, Abandon, AbandonRequest};
+use crate::game_log::{log_path, read_records};
+use crate::leaderboard::{format_date, not_typing, Leaderboard};
+use crate::puzzle::{PuzzlePack, PuzzleProgress, Puzzles};
+use crate::saves::{list_replays, read_replay, read_save, save_path};
+use crate::settings::{Settings, BOARD_SIZES};
+use crate::statistics::{spawn_statistics, Statistics};
+use crate::styles::{menu_button_style, overlay_root};
+use crate::theme::{Theme, ThemeColor};
+use crate::ui::{settings_closed, spawn_button, SettingsScreen};
+use bevy::app::AppExit;
+use bevy::prelude::*;
+use std::path::PathBuf;
+
+/// Every menu button is this wide, so they line up.
+const BUTTON_WIDTH: f32 = 260.0;
+/// Replays listed in the menu, the newest ones.
+const LISTED_REPLAYS: usize = 10;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MenuPage {
+    Main,
+    NewGame,
+    Puzzles,
+    Replays,
+    Statistics,
+    Leaderboard,
+}
+
+#[derive(Resource)]
+pub struct Menu {
+    pub page: MenuPage,
+    /// Board size and mode picked for a new game.
+    pub board_size: u8,
+    pub mode: GameMode,
+    /// Set when the menu was opened during a game, which can be resumed.
+    pub resume: bool,
+    pub message: String,
+}
+
+impl Default for Menu {
+    fn default() -> Self {
+        Menu {
+            page: MenuPage::Main,
+            board_size: 4,
+            mode: GameMode::Classic,
+            resume: false,
+            message: String::new(),
+        }
+    }
+}
+
+#[derive(Component)]
+struct MenuRoot;
+
+#[derive(Component)]
+struct MenuItems;
+
+#[derive(Component)]
+struct MenuMessage;
+
+#[derive(Component, Debug, Clone, PartialEq, Eq)]
+enum MenuButton {
+    Resume,
+    Open(MenuPage),
+    Continue,
+    Settings,
+    Quit,
+    BoardSize,
+    Mode,
+    Start,
+    Puzzle(usize),
+    Replay(PathBuf),
+    Back,
+}
+
+/// Press of a button picked without the mouse.
+#[derive(Event)]
+struct MenuPress(MenuButton);
+
+pub struct MenuPlugin;
+impl Plugin for MenuPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Menu>()
+            .add_event::<MenuPress>()
+            .add_systems(Startup, (pick_board_size, setup_menu))
+            // Before the binding screen sees the key, so closing it or
+            // the settings with Escape doesn't open the menu as well
+            .add_systems(
+                PreUpdate,
+                toggle_menu
+                    .run_if(screen_closed)
+                    .run_if(settings_closed)
+                    .run_if(not_typing)
+                    .run_if(no_confirmation)
+                    .after(bevy::input::InputSystem),
+            )
+            .add_systems(
+                Update,
+                (
+                    navigate_menu
+                        .run_if(in_state(RunState::Menu))
+                        .run_if(screen_closed)
+                        .run_if(settings_closed)
+                        .run_if(no_confirmation),
+                    menu_buttons,
+                    render_menu,
+                )
+                    .chain(),
+            );
+    }
+}
+
+// The command line size is the default pick
+fn pick_board_size(next_game: Res<NextGame>, mut menu: ResMut<Menu>) {
+    menu.board_size = next_game.board_size;
+}
+
+pub fn toggle_menu(
+    input: Res<Input<KeyCode>>,
+    run_state: Res<State<RunState>>,
+    mut next_state: ResMut<NextState<RunState>>,
+    mut menu: ResMut<Menu>,
+) {
+    if !input.just_pressed(KeyCode::Escape) {
+        return;
+    }
+    match run_state.get() {
+        RunState::Menu if menu.page != MenuPage::Main => menu.page = MenuPage::Main,
+        RunState::Menu if menu.resume => next_state.set(RunState::Playing),
+        RunState::Menu => {}
+        // A finished game is already recorded, entering GameOver again would record it twice
+        playing => {
+            menu.page = MenuPage::Main;
+            menu.resume = *playing != RunState::GameOver;
+            menu.message.clear();
+            next_state.set(RunState::Menu);
+        }
+    }
+}
+
+// Shifts move the focus through the buttons from the top, confirming presses
+// the focused one and undoing goes back to the main page.
+fn navigate_menu(
+    mut action_reader: EventReader<ActionEvent>,
+    mut button_query: Query<(&MenuButton, &Interaction, &GlobalTransform, &mut BackgroundColor)>,
+    theme: Res<Theme>,
+    mut menu: ResMut<Menu>,
+    // None until a button is picked this way, so the mouse sees no focus
+    mut focus: Local<Option<usize>>,
+    mut focus_page: Local<Option<MenuPage>>,
+    mut press_writer: EventWriter<MenuPress>,
+) {
+    if *focus_page != Some(menu.page) {
+        *focus_page = Some(menu.page);
+        *focus = focus.map(|_| 0);
+    }
+    let mut buttons: Vec<_> = button_query.iter_mut().collect();
+    if buttons.is_empty() {
+        return;
+    }
+    buttons.sort_by(|a, b| {
+        let (a, b) = (a.2.translation(), b.2.translation());
+        a.y.total_cmp(&b.y).then(a.x.total_cmp(&b.x))
+    });
+    let last = buttons.len() - 1;
+    for ActionEvent(action) in action_reader.iter() {
+        match action {
+            Action::ShiftUp | Action::ShiftLeft => {
+                *focus = Some(focus.map_or(0, |index| index.saturating_sub(1)));
+            }
+            Action::ShiftDown | Action::ShiftRight => {
+                *focus = Some(focus.map_or(0, |index| (index + 1).min(last)));
+            }
+            Action::Confirm => {
+                if let Some(index) = *focus {
+                    press_writer.send(MenuPress(buttons[index.min(last)].0.clone()));
+                }
+            }
+            Action::Undo if menu.page != MenuPage::Main => menu.page = MenuPage::Main,
+            _ => {}
+        }
+    }
+    let Some(index) = *focus else {
+        return;
+    };
+    // Buttons under the mouse keep their hover color
+    for (position, (_, interaction, _, color)) in buttons.iter_mut().enumerate() {
+        if **interaction == Interaction::None {
+            let shown = if position == index.min(last) {
+                Interaction::Hovered
+            } else {
+                Interaction::None
+            };
+            **color = theme.button_color(shown);
+        }
+    }
+}
+
+fn menu_buttons(
+    mut interaction_query: Query<(&Interaction, &mut BackgroundColor, &MenuButton), Changed<Interaction>>,
+    mut press_reader: EventReader<MenuPress>,
+    theme: Res<Theme>,
+    mut menu: ResMut<Menu>,
+    mut next_state: ResMut<NextState<RunState>>,
+    settings: Res<Settings>,
+    mut settings_screen: ResMut<SettingsScreen>,
+    mut abandon_writer: EventWriter<AbandonRequest>,
+    mut exit: EventWriter<AppExit>,
+) {
+    let mut pressed: Vec<MenuButton> = press_reader.iter().map(|MenuPress(button)| button.clone()).collect();
+    for (interaction, mut color, button) in interaction_query.iter_mut() {
+        *color = theme.button_color(*interaction);
+        if *interaction == Interaction::Pressed {
+            pressed.push(button.clone());
+        }
+    }
+    for button in pressed {
+        menu.message.clear();
+        match &button {
+            MenuButton::Resume => next_state.set(RunState::Playing),
+            MenuButton::Open(page) => menu.page = *page,
+            // Leaving a game the menu was opened from asks first
+            MenuButton::Continue => match read_save(&save_path()) {
+                Ok(save) => abandon_writer.send(AbandonRequest(Abandon::Start(save))),
+                Err(err) => menu.message = format!("Can't continue: {err}"),
+            },
+            MenuButton::Settings => settings_screen.open = true,
+            MenuButton::Quit => exit.send(AppExit),
+            MenuButton::BoardSize => {
+                menu.board_size = if menu.board_size >= *BOARD_SIZES.end() {
+                    *BOARD_SIZES.start()
+                } else {
+                    menu.board_size + 1
+                }
+            }
+            MenuButton::Mode => {
+                menu.mode = match menu.mode {
+                    GameMode::Classic => GameMode::Puzzle,
+                    GameMode::Puzzle => GameMode::Classic,
+                }
+            }
+            // Puzzles come with their own boards, one is picked from the list
+            MenuButton::Start => match menu.mode {
+                GameMode::Classic => abandon_writer.send(AbandonRequest(Abandon::Start(NextGame {
+                    board_size: menu.board_size,
+                    ..settings.next_game()
+                }))),
+                GameMode::Puzzle => menu.page = MenuPage::Puzzles,
+            },
+            MenuButton::Puzzle(index) => abandon_writer.send(AbandonRequest(Abandon::Puzzle(*index))),
+            MenuButton::Replay(path) => match read_replay(path) {
+                Ok(replay) => abandon_writer.send(AbandonRequest(Abandon::Replay(replay))),
+                Err(err) => menu.message = format!("Can't play the replay: {err}"),
+            },
+            MenuButton::Back => menu.page = MenuPage::Main,
+        }
+    }
+}
+
+fn render_menu(
+    mut commands: Commands,
+    run_state: Res<State<RunState>>,
+    menu: Res<Menu>,
+    mut pack_events: EventReader<AssetEvent<PuzzlePack>>,
+    puzzles: Res<Puzzles>,
+    packs: Res<Assets<PuzzlePack>>,
+    progress: Res<PuzzleProgress>,
+    leaderboard: Res<Leaderboard>,
+    mut root_query: Query<&mut Style, With<MenuRoot>>,
+    items_query: Query<Entity, With<MenuItems>>,
+    mut message_query: Query<&mut Text, With<MenuMessage>>,
+    font_spec: Res<FontSpec>,
+) {
+    let pack_changed = pack_events.iter().count() > 0;
+    if !run_state.is_changed() && !menu.is_changed() && !pack_changed {
+        return;
+    }
+    let open = *run_state.get() == RunState::Menu;
+    root_query.single_mut().display = if open { Display::Flex } else { Display::None };
+    if !open {
+        return;
+    }
+    message_query.single_mut().sections[0].value = menu.message.clone();
+
+    let items = items_query.single();
+    commands.entity(items).despawn_descendants();
+    commands.entity(items).with_children(|parent| match menu.page {
+        MenuPage::Main => {
+            if menu.resume {
+                spawn_button(
+                    parent,
+                    &font_spec,
+                    "Resume",
+                    MenuButton::Resume,
+                    menu_button_style(BUTTON_WIDTH),
+                );
+            }
+            spawn_button(
+                parent,
+                &font_spec,
+                "New game",
+                MenuButton::Open(MenuPage::NewGame),
+                menu_button_style(BUTTON_WIDTH),
+            );
+            if save_path().exists() {
+                spawn_button(
+                    parent,
+                    &font_spec,
+                    "Continue",
+                    MenuButton::Continue,
+                    menu_button_style(BUTTON_WIDTH),
+                );
+            }
+            spawn_button(
+                parent,
+                &font_spec,
+                "Puzzles",
+                MenuButton::Open(MenuPage::Puzzles),
+                menu_button_style(BUTTON_WIDTH),
+            );
+            spawn_button(
+                parent,
+                &font_spec,
+                "Replays",
+                MenuButton::Open(MenuPage::Replays),
+                menu_button_style(BUTTON_WIDTH),
+            );
+            spawn_button(
+                parent,
+                &font_spec,
+                "Statistics",
+                MenuButton::Open(MenuPage::Statistics),
+                menu_button_style(BUTTON_WIDTH),
+            );
+            spawn_button(
+                parent,
+                &font_spec,
+                "Leaderboard",
+                MenuButton::Open(MenuPage::Leaderboard),
+                menu_button_style(BUTTON_WIDTH),
+            );
+            spawn_button(
+                parent,
+                &font_spec,
+                "Settings",
+                MenuButton::Settings,
+                menu_button_style(BUTTON_WIDTH),
+            );
+            spawn_button(parent, &font_spec, "Quit", MenuButton::Quit, menu_button_style(BUTTON_WIDTH));
+        }
+        MenuPage::NewGame => {
+            let size = format!("Board: {0}x{0}", menu.board_size);
+            spawn_button(
+                parent,
+                &font_spec,
+                &size,
+                MenuButton::BoardSize,
+                menu_button_style(BUTTON_WIDTH),
+            );
+            let mode = format!("Mode: {:?}", menu.mode);
+            spawn_button(parent, &font_spec, &mode, MenuButton::Mode, menu_button_style(BUTTON_WIDTH));
+            spawn_button(parent, &font_spec, "Start", MenuButton::Start, menu_button_style(BUTTON_WIDTH));
+            spawn_button(parent, &font_spec, "Back", MenuButton::Back, menu_button_style(BUTTON_WIDTH));
+        }
+        MenuPage::Puzzles => {
+            match packs.get(&puzzles.pack) {
+                Some(pack) => {
+                    for (index, puzzle) in pack.puzzles.iter().enumerate() {
+                        let label = match progress.best_moves.get(&puzzle.name) {
+                            Some(moves) => format!("{} ({} moves)", puzzle.name, moves),
+                            None => puzzle.name.clone(),
+                        };
+                        spawn_button(
+                            parent,
+                            &font_spec,
+                            &label,
+                            MenuButton::Puzzle(index),
+                            menu_button_style(BUTTON_WIDTH),
+                        );
+                    }
+                }
+                None => menu_text(parent, &font_spec, "Loading puzzles..."),
+            }
+            spawn_button(parent, &font_spec, "Back", MenuButton::Back, menu_button_style(BUTTON_WIDTH));
+        }
+        MenuPage::Replays => {
+            let replays: Vec<_> = list_replays()
+                .into_iter()
+                .take(LISTED_REPLAYS)
+                .filter_map(|path| Some((read_replay(&path).ok()?, path)))
+                .collect();
+            if replays.is_empty() {
+                menu_text(parent, &font_spec, "No replays yet");
+            }
+            for (replay, path) in replays {
+                let label = format!(
+                    "{0}x{0} {1:?}, {2} moves",
+                    replay.game.board_size,
+                    replay.game.mode,
+                    replay.shifts.len()
+                );
+                spawn_button(
+                    parent,
+                    &font_spec,
+                    &label,
+                    MenuButton::Replay(path),
+                    menu_button_style(BUTTON_WIDTH),
+                );
+            }
+            spawn_button(parent, &font_spec, "Back", MenuButton::Back, menu_button_style(BUTTON_WIDTH));
+        }
+        MenuPage::Statistics => {
+            match read_records(&log_path()) {
+                Ok(records) if records.is_empty() => {
+                    menu_text(parent, &font_spec, "No finished games yet")
+                }
+                Ok(records) => spawn_statistics(parent, &font_spec, &Statistics::new(&records)),
+                Err(err) => menu_text(parent, &font_spec, &format!("Can't read the game log: {err}")),
+            }
+            spawn_button(parent, &font_spec, "Back", MenuButton::Back, menu_button_style(BUTTON_WIDTH));
+        }
+        MenuPage::Leaderboard => {
+            let size = format!("Board: {0}x{0}", menu.board_size);
+            spawn_button(
+                parent,
+                &font_spec,
+                &size,
+                MenuButton::BoardSize,
+                menu_button_style(BUTTON_WIDTH),
+            );
+            let mode = format!("Mode: {:?}", menu.mode);
+            spawn_button(parent, &font_spec, &mode, MenuButton::Mode, menu_button_style(BUTTON_WIDTH));
+            let entries = leaderboard.entries(menu.board_size, menu.mode);
+            let table = if entries.is_empty() {
+                "No scores yet".to_string()
+            } else {
+                entries
+                    .iter()
+                    .enumerate()
+                    .map(|(index, entry)| {
+                        format!(
+                            "{}. {}  {} points, {} tile, {} moves, {}",
+                            index + 1,
+                            entry.name,
+                            entry.score,
+                            entry.highest_tile,
+                            entry.moves,
+                            format_date(entry.timestamp)
+                        )
+                    })
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            };
+            menu_text(parent, &font_spec, &table);
+            spawn_button(parent, &font_spec, "Back", MenuButton::Back, menu_button_style(BUTTON_WIDTH));
+        }
+    });
+}
+
+fn menu_text(parent: &mut ChildBuilder, font_spec: &FontSpec, text: &str) {
+    parent.spawn((
+        TextBundle::from_section(
+            text,
+            TextStyle {
+                font: font_spec.family.clone(),
+                font_size: 20.0,
+                color: Color::WHITE,
+            },
+        )
+        .with_text_alignment(TextAlignment::Center),
+        ThemeColor::Text,
+    ));
+}
+
+fn setup_menu(mut commands: Commands, font_spec: Res<FontSpec>) {
+    // Covers the game underneath, below the binding screen
+    let mut root = overlay_root(6);
+    root.style.display = Display::Flex;
+    root.style.flex_direction = FlexDirection::Column;
+    root.style.row_gap = Val::Px(20.0);
+    commands
+        .spawn((
+            root,
+            ThemeColor::Background,
+            MenuRoot,
+        ))
+        .with_children(|parent| {
+            parent.spawn((
+                TextBundle::from_section(
+                    "2048",
+                    TextStyle {
+                        font: font_spec.family.clone(),
+                        font_size: 60.0,
+                        color: Color::WHITE,
+                    },
+                ),
+                ThemeColor::Text,
+            ));
+            parent.spawn((
+                NodeBundle {
+                    style: Style {
+                        flex_direction: FlexDirection::Column,
+                        align_items: AlignItems::Center,
+                        row_gap: Val::Px(8.0),
+                        ..default()
+                    },
+                    ..default()
+                },
+                MenuItems,
+            ));
+            parent.spawn((
+                TextBundle::from_section(
+                    "",
+                    TextStyle {
+                        font: font_spec.family.clone(),
+                        font_size: 15.0,
+                        color: Color::WHITE,
+                    },
+                ),
+                ThemeColor::Text,
+                MenuMessage,
+            ));
+        });
+}
@@ -0,0 +1,155 @@
+use crate::components::{FontSpec, RunState};
+use crate::confirm::{Abandon, AbandonRequest};
+use crate::menu::{Menu, MenuPage};
+use crate::styles::{menu_button_style, overlay_root, DIMMED};
+use crate::theme::{Theme, ThemeColor};
+use crate::ui::{spawn_button, SettingsScreen};
+use bevy::prelude::*;
+use bevy::window::WindowFocused;
+
+const BUTTON_WIDTH: f32 = 200.0;
+
+#[derive(Component)]
+struct PauseRoot;
+
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+enum PauseButton {
+    Resume,
+    Restart,
+    Settings,
+    MainMenu,
+}
+
+pub struct PausePlugin;
+impl Plugin for PausePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Startup, setup_pause_menu)
+            .add_systems(
+                Update,
+                (
+                    pause_on_focus_loss.run_if(in_state(RunState::Playing)),
+                    pause_buttons,
+                    render_pause_menu,
+                )
+                    .chain(),
+            );
+    }
+}
+
+fn pause_on_focus_loss(
+    mut focus_reader: EventReader<WindowFocused>,
+    mut next_state: ResMut<NextState<RunState>>,
+) {
+    if focus_reader.iter().any(|event| !event.focused) {
+        next_state.set(RunState::Paused);
+    }
+}
+
+fn pause_buttons(
+    mut interaction_query: Query<(&Interaction, &mut BackgroundColor, &PauseButton), Changed<Interaction>>,
+    theme: Res<Theme>,
+    mut next_state: ResMut<NextState<RunState>>,
+    mut menu: ResMut<Menu>,
+    mut settings_screen: ResMut<SettingsScreen>,
+    mut abandon_writer: EventWriter<AbandonRequest>,
+) {
+    for (interaction, mut color, button) in interaction_query.iter_mut() {
+        *color = theme.button_color(*interaction);
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+        match button {
+            PauseButton::Resume => next_state.set(RunState::Playing),
+            PauseButton::Restart => abandon_writer.send(AbandonRequest(Abandon::Restart)),
+            PauseButton::Settings => settings_screen.open = true,
+            PauseButton::MainMenu => {
+                menu.page = MenuPage::Main;
+                menu.resume = true;
+                menu.message.clear();
+                next_state.set(RunState::Menu);
+            }
+        }
+    }
+}
+
+fn render_pause_menu(
+    run_state: Res<State<RunState>>,
+    mut root_query: Query<&mut Style, With<PauseRoot>>,
+) {
+    if !run_state.is_changed() {
+        return;
+    }
+    root_query.single_mut().display = if *run_state.get() == RunState::Paused {
+        Display::Flex
+    } else {
+        Display::None
+    };
+}
+
+fn setup_pause_menu(mut commands: Commands, font_spec: Res<FontSpec>) {
+    commands
+        .spawn((
+            NodeBundle {
+                background_color: BackgroundColor(DIMMED),
+                ..overlay_root(5)
+            },
+            PauseRoot,
+        ))
+        .with_children(|parent| {
+            parent
+                .spawn((
+                    NodeBundle {
+                        style: Style {
+                            flex_direction: FlexDirection::Column,
+                            align_items: AlignItems::Center,
+                            row_gap: Val::Px(10.0),
+                            padding: UiRect::all(Val::Px(20.0)),
+                            ..default()
+                        },
+                        ..default()
+                    },
+                    ThemeColor::Board,
+                ))
+                .with_children(|parent| {
+                    parent.spawn((
+                        TextBundle::from_section(
+                            "Paused",
+                            TextStyle {
+                                font: font_spec.family.clone(),
+                                font_size: 30.0,
+                                color: Color::WHITE,
+                            },
+                        ),
+                        ThemeColor::Text,
+                    ));
+                    spawn_button(
+                        parent,
+                        &font_spec,
+                        "Resume",
+                        PauseButton::Resume,
+                        menu_button_style(BUTTON_WIDTH),
+                    );
+                    spawn_button(
+                        parent,
+                        &font_spec,
+                        "Restart",
+                        PauseButton::Restart,
+                        menu_button_style(BUTTON_WIDTH),
+                    );
+                    spawn_button(
+                        parent,
+                        &font_spec,
+                        "Settings",
+                        PauseButton::Settings,
+                        menu_button_style(BUTTON_WIDTH),
+                    );
+                    spawn_button(
+                        parent,
+                        &font_spec,
+                        "Main menu",
+                        PauseButton::MainMenu,
+                        menu_button_style(BUTTON_WIDTH),
+                    );
+                });
+        });
+}
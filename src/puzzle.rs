@@ -0,0 +1,320 @@
+use crate::components::{FontSpec, Game, GameMode, NextGame, Points, Position, RunState};
+use crate::settings::BOARD_SIZES;
+use crate::storage::{load_json, save_json};
+use crate::theme::ThemeColor;
+use crate::timeline::is_live;
+use crate::utility::NewGameEvent;
+use bevy::{
+    asset::{AssetLoader, LoadContext, LoadedAsset},
+    prelude::*,
+    reflect::{TypePath, TypeUuid},
+    utils::BoxedFuture,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+const PROGRESS_FILE: &str = "puzzles.json";
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub struct PuzzleTile {
+    pub x: u8,
+    pub y: u8,
+    pub value: u32,
+}
+
+impl PuzzleTile {
+    fn to_tile(self) -> (Position, Points) {
+        (Position { x: self.x, y: self.y }, Points { value: self.value })
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub enum SpawnSequence {
+    /// Spawns in this order, random ones once the list runs out.
+    /// A spawn landing on a tile is reported and skipped.
+    Fixed(Vec<PuzzleTile>),
+    Seeded(u64),
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub enum PuzzleGoal {
+    ReachTile(u32),
+    ReachScore(u32),
+    ClearTo { tiles: usize, moves: u32 },
+}
+
+impl PuzzleGoal {
+    pub fn describe(&self) -> String {
+        match self {
+            PuzzleGoal::ReachTile(value) => format!("Reach a {value} tile"),
+            PuzzleGoal::ReachScore(score) => format!("Score {score} points"),
+            PuzzleGoal::ClearTo { tiles, moves } => {
+                format!("Clear to {tiles} tiles in {moves} moves")
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Puzzle {
+    pub name: String,
+    pub size: u8,
+    pub tiles: Vec<PuzzleTile>,
+    pub spawns: SpawnSequence,
+    pub goal: PuzzleGoal,
+}
+
+impl Puzzle {
+    fn validate(&self) -> Result<(), String> {
+        if !BOARD_SIZES.contains(&self.size) {
+            return Err(format!("puzzle '{}' needs a board between 3 and 8", self.name));
+        }
+        let mut occupied = HashSet::new();
+        for tile in self.tiles.iter() {
+            if !occupied.insert((tile.x, tile.y)) {
+                return Err(format!(
+                    "puzzle '{}' starts with two tiles at ({}, {})",
+                    self.name, tile.x, tile.y
+                ));
+            }
+        }
+        let spawns = match &self.spawns {
+            SpawnSequence::Fixed(spawns) => spawns.as_slice(),
+            SpawnSequence::Seeded(_) => &[],
+        };
+        for tile in self.tiles.iter().chain(spawns) {
+            if tile.x >= self.size || tile.y >= self.size {
+                return Err(format!(
+                    "puzzle '{}' has a tile at ({}, {}) outside of its board",
+                    self.name, tile.x, tile.y
+                ));
+            }
+            if !tile.value.is_power_of_two() || tile.value < 2 {
+                return Err(format!(
+                    "puzzle '{}' has a tile of {}, which is not a power of two",
+                    self.name, tile.value
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn next_game(&self) -> NextGame {
+        let (seed, spawns) = match &self.spawns {
+            SpawnSequence::Fixed(spawns) => {
+                (0, spawns.iter().map(|tile| tile.to_tile()).collect())
+            }
+            SpawnSequence::Seeded(seed) => (*seed, Default::default()),
+        };
+        NextGame {
+            board_size: self.size,
+            mode: GameMode::Puzzle,
+            seed: Some(seed),
+            tiles: self.tiles.iter().map(|tile| tile.to_tile()).collect(),
+            spawns,
+            ..default()
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, TypeUuid, TypePath)]
+#[uuid = "1a2e68d5-706b-4e91-95e2-f343c0083e0a"]
+pub struct PuzzlePack {
+    pub puzzles: Vec<Puzzle>,
+}
+
+#[derive(Default)]
+pub struct PuzzlePackLoader;
+
+impl AssetLoader for PuzzlePackLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), bevy::asset::Error>> {
+        Box::pin(async move {
+            let pack = ron::de::from_bytes::<PuzzlePack>(bytes)?;
+            for puzzle in pack.puzzles.iter() {
+                puzzle.validate().map_err(bevy::asset::Error::msg)?;
+            }
+            load_context.set_default_asset(LoadedAsset::new(pack));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["puzzles.ron"]
+    }
+}
+
+#[derive(Resource)]
+pub struct Puzzles {
+    pub pack: Handle<PuzzlePack>,
+}
+
+#[derive(Default, Resource)]
+pub struct ActivePuzzle {
+    /// Index into the pack of the puzzle being played.
+    pub index: Option<usize>,
+    pub solved: bool,
+}
+
+/// Puzzle to start as soon as the pack is loaded.
+#[derive(Default, Resource)]
+pub struct PuzzleRequest(pub Option<usize>);
+
+/// Best move count of every solved puzzle, by name.
+#[derive(Default, Resource, Serialize, Deserialize)]
+pub struct PuzzleProgress {
+    pub best_moves: HashMap<String, u32>,
+}
+
+#[derive(Component)]
+pub struct PuzzleStatus;
+
+pub struct PuzzlePlugin;
+impl Plugin for PuzzlePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_asset::<PuzzlePack>()
+            .init_asset_loader::<PuzzlePackLoader>()
+            .init_resource::<ActivePuzzle>()
+            .init_resource::<PuzzleRequest>()
+            .add_systems(Startup, (load_puzzles, setup_puzzle_status))
+            .add_systems(PreUpdate, reset_puzzle.run_if(on_event::<NewGameEvent>()))
+            .add_systems(
+                Update,
+                (start_requested_puzzle, render_puzzle_status),
+            )
+            .add_systems(
+                PostUpdate,
+                check_puzzle_goal
+                    .run_if(in_state(RunState::Playing))
+                    .run_if(is_live),
+            );
+    }
+}
+
+fn load_puzzles(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.insert_resource(Puzzles {
+        pack: asset_server.load("puzzles/basics.puzzles.ron"),
+    });
+    commands.insert_resource(load_json::<PuzzleProgress>(PROGRESS_FILE));
+}
+
+fn reset_puzzle(mut active: ResMut<ActivePuzzle>) {
+    active.solved = false;
+}
+
+fn start_requested_puzzle(
+    mut request: ResMut<PuzzleRequest>,
+    puzzles: Res<Puzzles>,
+    packs: Res<Assets<PuzzlePack>>,
+    mut active: ResMut<ActivePuzzle>,
+    mut next_game: ResMut<NextGame>,
+    mut new_game_writer: EventWriter<NewGameEvent>,
+) {
+    let Some(index) = request.0 else {
+        return;
+    };
+    let Some(pack) = packs.get(&puzzles.pack) else {
+        return;
+    };
+    request.0 = None;
+    match pack.puzzles.get(index) {
+        Some(puzzle) => {
+            *next_game = puzzle.next_game();
+            active.index = Some(index);
+            new_game_writer.send(NewGameEvent);
+        }
+        // Only --puzzle can ask for one the pack doesn't have, so it fails like a bad option
+        None => {
+            eprintln!("error: there is no puzzle {}, the pack has {}", index, pack.puzzles.len());
+            std::process::exit(2);
+        }
+    }
+}
+
+fn check_puzzle_goal(
+    game: Res<Game>,
+    tiles: Query<&Points>,
+    puzzles: Res<Puzzles>,
+    packs: Res<Assets<PuzzlePack>>,
+    mut active: ResMut<ActivePuzzle>,
+    mut progress: ResMut<PuzzleProgress>,
+    mut run_state: ResMut<NextState<RunState>>,
+) {
+    if game.mode != GameMode::Puzzle || game.moves == 0 {
+        return;
+    }
+    let Some(puzzle) = active
+        .index
+        .and_then(|index| packs.get(&puzzles.pack)?.puzzles.get(index))
+    else {
+        return;
+    };
+    let (solved, out_of_moves) = match puzzle.goal {
+        PuzzleGoal::ReachTile(value) => (tiles.iter().any(|points| points.value >= value), false),
+        PuzzleGoal::ReachScore(score) => (game.score >= score, false),
+        PuzzleGoal::ClearTo { tiles: count, moves } => {
+            (tiles.iter().len() <= count, game.moves >= moves)
+        }
+    };
+    if solved {
+        active.solved = true;
+        let best = progress.best_moves.entry(puzzle.name.clone()).or_insert(game.moves);
+        *best = (*best).min(game.moves);
+        if let Err(err) = save_json(PROGRESS_FILE, &*progress) {
+            error!("Failed to save puzzle progress: {}", err);
+        }
+        run_state.set(RunState::GameOver);
+    } else if out_of_moves {
+        run_state.set(RunState::GameOver);
+    }
+}
+
+fn render_puzzle_status(
+    game: Res<Game>,
+    run_state: Res<State<RunState>>,
+    puzzles: Res<Puzzles>,
+    packs: Res<Assets<PuzzlePack>>,
+    active: Res<ActivePuzzle>,
+    mut status_query: Query<&mut Text, With<PuzzleStatus>>,
+) {
+    let puzzle = active
+        .index
+        .filter(|_| game.mode == GameMode::Puzzle)
+        .and_then(|index| packs.get(&puzzles.pack)?.puzzles.get(index));
+    let status = match (puzzle, run_state.get()) {
+        (None, _) => String::new(),
+        (Some(_), RunState::GameOver) if active.solved => {
+            format!("Solved in {} moves!", game.moves)
+        }
+        (Some(_), RunState::GameOver) => "Not solved, try again".to_string(),
+        (Some(puzzle), _) => format!("{}\nMoves: {}", puzzle.goal.describe(), game.moves),
+    };
+    let mut text = status_query.single_mut();
+    if text.sections[0].value != status {
+        text.sections[0].value = status;
+    }
+}
+
+fn setup_puzzle_status(mut commands: Commands, font_spec: Res<FontSpec>) {
+    commands.spawn((
+        TextBundle::from_section(
+            "",
+            TextStyle {
+                font: font_spec.family.clone(),
+                font_size: 15.0,
+                color: Color::WHITE,
+            },
+        )
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            left: Val::Px(50.0),
+            top: Val::Px(150.0),
+            ..default()
+        }),
+        ThemeColor::Text,
+        PuzzleStatus,
+    ));
+}
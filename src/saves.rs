@@ -0,0 +1,271 @@
+use crate::components::{Game, NextGame, Points, Position, RunState, SpawnQueue};
+use crate::settings::BOARD_SIZES;
+use crate::storage::{data_dir, save_json};
+use crate::timeline::is_live;
+use crate::utility::{BoardShift, MoveQueue, NewGameEvent};
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::{
+    cmp::Reverse,
+    collections::{HashSet, VecDeque},
+    fs, io,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+const SAVE_FILE: &str = "save.json";
+const REPLAY_DIR: &str = "replays";
+
+/// A finished game: how it was set up and every shift played.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Replay {
+    pub game: NextGame,
+    pub shifts: Vec<BoardShift>,
+}
+
+/// Replay of the game being played, kept once it ended.
+#[derive(Default, Resource)]
+pub struct LastReplay {
+    pub replay: Option<Replay>,
+    /// File the replay was saved to, saved over when the game ends again after an undo.
+    pub name: Option<String>,
+}
+
+#[derive(Resource)]
+pub struct ReplayPlayer {
+    /// Replay to play once its game has started.
+    pub pending: Option<Replay>,
+    pub shifts: VecDeque<BoardShift>,
+    /// The game being played is a replay, which isn't logged, ranked,
+    /// saved or undone.
+    pub playing: bool,
+    pub interval: Timer,
+}
+
+impl Default for ReplayPlayer {
+    fn default() -> Self {
+        ReplayPlayer {
+            pending: None,
+            shifts: VecDeque::new(),
+            playing: false,
+            interval: Timer::new(Duration::from_millis(300), TimerMode::Repeating),
+        }
+    }
+}
+
+pub fn save_path() -> PathBuf {
+    data_dir().join(SAVE_FILE)
+}
+
+/// Replay files of finished games, newest first.
+pub fn list_replays() -> Vec<PathBuf> {
+    let Ok(entries) = fs::read_dir(data_dir().join(REPLAY_DIR)) else {
+        return Vec::new();
+    };
+    let mut paths: Vec<PathBuf> = entries
+        .filter_map(|entry| Some(entry.ok()?.path()))
+        .filter(|path| path.extension().is_some_and(|extension| extension == "json"))
+        .collect();
+    paths.sort_by_key(|path| Reverse(replay_order(path)));
+    paths
+}
+
+// Named after the second they were saved, with a counter after it on a tie,
+// compared as numbers so `_10` comes after `_2`.
+fn replay_order(path: &Path) -> (u64, u64, String) {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let (timestamp, count) = stem.split_once('_').unwrap_or((stem.as_ref(), "0"));
+    (
+        timestamp.parse().unwrap_or_default(),
+        count.parse().unwrap_or_default(),
+        stem.to_string(),
+    )
+}
+
+/// Checks a game read from a file can be set up.
+fn validate_game(game: &NextGame) -> Result<(), String> {
+    if !BOARD_SIZES.contains(&game.board_size) {
+        return Err(format!("board size {} is not between 3 and 8", game.board_size));
+    }
+    if !(0.0..=1.0).contains(&game.four_chance) {
+        return Err(format!("chance of a 4 {} is not between 0 and 1", game.four_chance));
+    }
+    let mut occupied = HashSet::new();
+    for (position, _) in game.tiles.iter() {
+        if !occupied.insert(*position) {
+            return Err(format!("two tiles at ({}, {})", position.x, position.y));
+        }
+    }
+    for (position, points) in game.tiles.iter().chain(game.spawns.iter()) {
+        if position.x >= game.board_size || position.y >= game.board_size {
+            return Err(format!("tile at ({}, {}) outside of the board", position.x, position.y));
+        }
+        if !points.value.is_power_of_two() || points.value < 2 {
+            return Err(format!("tile of {}, which is not a power of two", points.value));
+        }
+    }
+    Ok(())
+}
+
+pub fn read_save(path: &Path) -> Result<NextGame, String> {
+    let contents = fs::read_to_string(path).map_err(|err| format!("{}: {}", path.display(), err))?;
+    let game: NextGame =
+        serde_json::from_str(&contents).map_err(|err| format!("{}: {}", path.display(), err))?;
+    // Every field has a default, a save without tiles isn't one
+    if game.tiles.is_empty() {
+        return Err(format!("{}: the save has no tiles", path.display()));
+    }
+    validate_game(&game).map_err(|err| format!("{}: {}", path.display(), err))?;
+    Ok(NextGame {
+        one_off: true,
+        ..game
+    })
+}
+
+pub fn read_replay(path: &Path) -> Result<Replay, String> {
+    let contents = fs::read_to_string(path).map_err(|err| format!("{}: {}", path.display(), err))?;
+    let replay: Replay =
+        serde_json::from_str(&contents).map_err(|err| format!("{}: {}", path.display(), err))?;
+    if replay.game.seed.is_none() {
+        return Err(format!("{}: a replay needs the seed of its game", path.display()));
+    }
+    validate_game(&replay.game).map_err(|err| format!("{}: {}", path.display(), err))?;
+    Ok(replay)
+}
+
+/// Sets up the replay's game, its shifts play once it started.
+pub fn start_replay(
+    replay: Replay,
+    next_game: &mut NextGame,
+    player: &mut ReplayPlayer,
+    new_game_writer: &mut EventWriter<NewGameEvent>,
+) {
+    *next_game = NextGame {
+        one_off: true,
+        ..replay.game.clone()
+    };
+    player.pending = Some(replay);
+    new_game_writer.send(NewGameEvent);
+}
+
+pub fn is_replaying(player: Res<ReplayPlayer>) -> bool {
+    !player.shifts.is_empty()
+}
+
+/// True from the start of a replay's game until the next game starts.
+pub fn is_playback(player: Res<ReplayPlayer>) -> bool {
+    player.playing
+}
+
+pub struct SavesPlugin;
+impl Plugin for SavesPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<LastReplay>()
+            .init_resource::<ReplayPlayer>()
+            .add_systems(PreUpdate, load_pending_replay.run_if(on_event::<NewGameEvent>()))
+            .add_systems(Update, play_replay.run_if(in_state(RunState::Playing)))
+            .add_systems(
+                PostUpdate,
+                autosave
+                    .run_if(in_state(RunState::Playing))
+                    .run_if(is_live)
+                    .run_if(not(is_playback)),
+            )
+            .add_systems(OnEnter(RunState::GameOver), keep_replay.run_if(not(is_playback)));
+    }
+}
+
+fn load_pending_replay(mut player: ResMut<ReplayPlayer>, mut last_replay: ResMut<LastReplay>) {
+    // A replay can be watched again from its game over
+    *last_replay = LastReplay {
+        replay: player.pending.clone(),
+        name: None,
+    };
+    player.playing = player.pending.is_some();
+    player.shifts = player
+        .pending
+        .take()
+        .map(|replay| replay.shifts.into())
+        .unwrap_or_default();
+    player.interval.reset();
+}
+
+fn play_replay(
+    time: Res<Time>,
+    mut player: ResMut<ReplayPlayer>,
+    mut move_queue: ResMut<MoveQueue>,
+) {
+    if player.shifts.is_empty() || !player.interval.tick(time.delta()).just_finished() {
+        return;
+    }
+    if move_queue.moves.is_empty() {
+        if let Some(board_shift) = player.shifts.pop_front() {
+            move_queue.moves.push_back(board_shift);
+        }
+    }
+}
+
+fn autosave(
+    game: Res<Game>,
+    tiles: Query<(&Position, &Points)>,
+    spawn_queue: Res<SpawnQueue>,
+    mut saved_moves: Local<Option<u32>>,
+) {
+    if game.moves == 0 || *saved_moves == Some(game.moves) {
+        return;
+    }
+    *saved_moves = Some(game.moves);
+    let save = NextGame {
+        board_size: game.setup.board_size,
+        mode: game.mode,
+        seed: Some(game.seed),
+        tiles: tiles.iter().map(|(pos, points)| (*pos, *points)).collect(),
+        spawns: spawn_queue.0.clone(),
+        four_chance: game.setup.four_chance,
+        score: game.score,
+        moves: game.moves,
+        one_off: true,
+    };
+    if let Err(err) = save_json(SAVE_FILE, &save) {
+        error!("Failed to save the game: {}", err);
+    }
+}
+
+fn keep_replay(game: Res<Game>, mut last_replay: ResMut<LastReplay>) {
+    if let Err(err) = fs::remove_file(save_path()) {
+        if err.kind() != io::ErrorKind::NotFound {
+            error!("Failed to remove the saved game: {}", err);
+        }
+    }
+    if game.shifts.is_empty() {
+        return;
+    }
+    let replay = Replay {
+        game: game.setup.clone(),
+        shifts: game.shifts.clone(),
+    };
+    let name = last_replay.name.clone().unwrap_or_else(new_replay_name);
+    if let Err(err) = save_json(&name, &replay) {
+        error!("Failed to save the replay: {}", err);
+    }
+    *last_replay = LastReplay {
+        replay: Some(replay),
+        name: Some(name),
+    };
+}
+
+// Games ending in the same second get a counter after the timestamp
+fn new_replay_name() -> String {
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default();
+    let mut name = format!("{REPLAY_DIR}/{timestamp}.json");
+    let mut count = 0;
+    while data_dir().join(&name).exists() {
+        count += 1;
+        name = format!("{REPLAY_DIR}/{timestamp}_{count}.json");
+    }
+    name
+}
+
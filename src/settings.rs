@@ -0,0 +1,104 @@
+use crate::components::NextGame;
+use crate::storage::{load_json, save_json};
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::ops::RangeInclusive;
+
+const SETTINGS_FILE: &str = "settings.json";
+
+pub const BOARD_SIZES: RangeInclusive<u8> = 3..=8;
+/// Chances of a new tile being a 4, the settings screen steps through them.
+pub const FOUR_CHANCES: [f32; 4] = [0.0, 0.1, 0.25, 0.5];
+pub const UNDO_LIMITS: [Option<u32>; 6] = [None, Some(0), Some(1), Some(3), Some(5), Some(10)];
+pub const MOVE_QUEUE_LENGTHS: [usize; 4] = [1, 2, 4, 8];
+
+/// Settings picked on the settings screen, new games are set up with them.
+#[derive(Resource, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub board_size: u8,
+    /// Chance of a new tile being a 4 rather than a 2.
+    pub four_chance: f32,
+    pub theme: String,
+    /// Percent, from 0 to 100.
+    pub volume: u8,
+    /// Undos allowed in one game, unlimited when `None`.
+    pub undo_limit: Option<u32>,
+    /// Ask before ending or restarting a game that is going.
+    pub confirm_abandon: bool,
+    /// Shifts kept while tiles are still sliding, at least 1.
+    pub move_queue: usize,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            board_size: 4,
+            four_chance: 0.0,
+            theme: "default".to_string(),
+            volume: 80,
+            undo_limit: None,
+            confirm_abandon: true,
+            move_queue: 4,
+        }
+    }
+}
+
+impl Settings {
+    /// Resets the values that are out of range to their defaults,
+    /// returning what was reset.
+    pub fn validate(&mut self) -> Vec<String> {
+        let defaults = Settings::default();
+        let mut problems = Vec::new();
+        if !BOARD_SIZES.contains(&self.board_size) {
+            problems.push(format!("board size {} is not between 3 and 8", self.board_size));
+            self.board_size = defaults.board_size;
+        }
+        if !(0.0..=1.0).contains(&self.four_chance) {
+            problems.push(format!("chance of a 4 {} is not between 0 and 1", self.four_chance));
+            self.four_chance = defaults.four_chance;
+        }
+        if self.theme.is_empty() {
+            problems.push("the theme has no name".to_string());
+            self.theme = defaults.theme;
+        }
+        if self.volume > 100 {
+            problems.push(format!("volume {} is above 100", self.volume));
+            self.volume = defaults.volume;
+        }
+        if self.move_queue == 0 {
+            problems.push("move queue can't be empty".to_string());
+            self.move_queue = defaults.move_queue;
+        }
+        problems
+    }
+
+    /// A fresh classic game.
+    pub fn next_game(&self) -> NextGame {
+        NextGame {
+            board_size: self.board_size,
+            four_chance: self.four_chance,
+            ..default()
+        }
+    }
+
+    pub fn can_undo(&self, undos: u32) -> bool {
+        !self.undo_limit.is_some_and(|limit| undos >= limit)
+    }
+}
+
+/// Loads the saved settings, values that can't be used are reset and reported.
+pub fn load_settings() -> (Settings, Vec<String>) {
+    let mut settings = load_json::<Settings>(SETTINGS_FILE);
+    let problems = settings.validate();
+    for problem in problems.iter() {
+        warn!("Resetting a setting, {}", problem);
+    }
+    (settings, problems)
+}
+
+pub fn save_settings(settings: &Settings) {
+    if let Err(err) = save_json(SETTINGS_FILE, settings) {
+        error!("Failed to save the settings: {}", err);
+    }
+}
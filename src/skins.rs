@@ -0,0 +1,234 @@
+use crate::bindings::screen_closed;
+use crate::components::{Points, TileText};
+use bevy::{
+    asset::{AssetLoader, AssetPath, LoadContext, LoadState, LoadedAsset},
+    prelude::*,
+    reflect::{TypePath, TypeUuid},
+    utils::BoxedFuture,
+};
+use serde::Deserialize;
+use std::collections::HashMap;
+
+const SKIN_DIR: &str = "skins";
+const SKIN_EXTENSION: &str = ".skin.ron";
+
+/// Tile textures from a sprite atlas, loaded from `assets/skins/<name>.skin.ron`.
+#[derive(Debug, Deserialize, TypeUuid, TypePath)]
+#[uuid = "3c7d2a4b-8e1f-4b6a-a0d9-5f2e7c1b9a84"]
+pub struct Skin {
+    /// Atlas image, relative to the assets folder.
+    pub texture: String,
+    pub tile_size: (f32, f32),
+    pub columns: usize,
+    pub rows: usize,
+    /// Atlas index of each tile value, values left out use the theme colors.
+    pub tiles: HashMap<u32, usize>,
+    /// Draw the number over the texture.
+    #[serde(default = "show_text")]
+    pub show_text: bool,
+    #[serde(skip)]
+    pub image: Handle<Image>,
+}
+
+fn show_text() -> bool {
+    true
+}
+
+#[derive(Default)]
+pub struct SkinLoader;
+
+impl AssetLoader for SkinLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), bevy::asset::Error>> {
+        Box::pin(async move {
+            let mut skin = ron::de::from_bytes::<Skin>(bytes)?;
+            if let Some(index) = skin.tiles.values().find(|index| **index >= skin.columns * skin.rows) {
+                return Err(bevy::asset::Error::msg(format!(
+                    "atlas index {} is outside of the {}x{} grid",
+                    index, skin.columns, skin.rows
+                )));
+            }
+            let texture = AssetPath::from(skin.texture.clone());
+            skin.image = load_context.get_handle(texture.clone());
+            load_context.set_default_asset(LoadedAsset::new(skin).with_dependency(texture));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["skin.ron"]
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct Skins {
+    /// Name of the active skin, tiles are flat colors without one.
+    pub active: Option<String>,
+    pub available: Vec<(String, Handle<Skin>)>,
+}
+
+impl Skins {
+    fn handle(&self) -> Option<&Handle<Skin>> {
+        let active = self.active.as_ref()?;
+        self.available
+            .iter()
+            .find(|(name, _)| name == active)
+            .map(|(_, handle)| handle)
+    }
+
+    /// Goes through the skins, then back to flat colors.
+    pub fn cycle(&mut self) {
+        let next = match &self.active {
+            None => 0,
+            Some(active) => self
+                .available
+                .iter()
+                .position(|(name, _)| name == active)
+                .map_or(0, |index| index + 1),
+        };
+        self.active = self.available.get(next).map(|(name, _)| name.clone());
+    }
+}
+
+/// The atlas of the active skin once its texture loaded.
+#[derive(Resource, Default)]
+pub struct ActiveSkin {
+    pub atlas: Option<Handle<TextureAtlas>>,
+    pub tiles: HashMap<u32, usize>,
+    pub show_text: bool,
+}
+
+/// Textured sprite drawn between a tile's colored sprite and its text.
+#[derive(Component)]
+pub struct TileSkin;
+
+pub struct SkinPlugin;
+impl Plugin for SkinPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_asset::<Skin>()
+            .init_asset_loader::<SkinLoader>()
+            .init_resource::<Skins>()
+            .init_resource::<ActiveSkin>()
+            .add_systems(Startup, load_skins)
+            .add_systems(Update, (cycle_skin.run_if(screen_closed), use_active_skin).chain())
+            .add_systems(PostUpdate, render_tile_skins);
+    }
+}
+
+fn load_skins(asset_server: Res<AssetServer>, mut skins: ResMut<Skins>) {
+    // The folder holds the atlas images as well, only skin files are kept
+    let handles = asset_server.load_folder(SKIN_DIR).unwrap_or_else(|err| {
+        error!("Can't load the skins: {:?}", err);
+        Vec::new()
+    });
+    for handle in handles {
+        let Some(path) = asset_server.get_handle_path(&handle) else {
+            continue;
+        };
+        let file_name = path.path().file_name().and_then(|name| name.to_str());
+        if let Some(name) = file_name.and_then(|name| name.strip_suffix(SKIN_EXTENSION)) {
+            skins.available.push((name.to_string(), handle.typed()));
+        }
+    }
+    skins.available.sort_by(|a, b| a.0.cmp(&b.0));
+}
+
+fn cycle_skin(input: Res<Input<KeyCode>>, mut skins: ResMut<Skins>) {
+    if input.just_pressed(KeyCode::F5) {
+        skins.cycle();
+        info!("Skin: {}", skins.active.as_deref().unwrap_or("none"));
+    }
+}
+
+// Builds the atlas once the active skin and its texture loaded,
+// a texture that fails to load leaves the tiles flat.
+fn use_active_skin(
+    mut skin_events: EventReader<AssetEvent<Skin>>,
+    mut pending: Local<bool>,
+    skins: Res<Skins>,
+    skin_assets: Res<Assets<Skin>>,
+    asset_server: Res<AssetServer>,
+    mut atlases: ResMut<Assets<TextureAtlas>>,
+    mut active: ResMut<ActiveSkin>,
+) {
+    if skins.is_changed() || skin_events.iter().count() > 0 {
+        *pending = true;
+    }
+    if !*pending {
+        return;
+    }
+    let Some(handle) = skins.handle() else {
+        *active = ActiveSkin::default();
+        *pending = false;
+        return;
+    };
+    let Some(skin) = skin_assets.get(handle) else {
+        return;
+    };
+    match asset_server.get_load_state(&skin.image) {
+        LoadState::Loaded => {
+            let atlas = TextureAtlas::from_grid(
+                skin.image.clone(),
+                Vec2::new(skin.tile_size.0, skin.tile_size.1),
+                skin.columns,
+                skin.rows,
+                None,
+                None,
+            );
+            *active = ActiveSkin {
+                atlas: Some(atlases.add(atlas)),
+                tiles: skin.tiles.clone(),
+                show_text: skin.show_text,
+            };
+        }
+        LoadState::Failed => {
+            warn!("Can't load {}, tiles stay flat", skin.texture);
+            *active = ActiveSkin::default();
+        }
+        _ => return,
+    }
+    *pending = false;
+}
+
+// Runs in PostUpdate like the tile colors, so new tiles are skinned before they're drawn.
+fn render_tile_skins(
+    active: Res<ActiveSkin>,
+    tiles: Query<(Ref<Points>, &Children)>,
+    mut skinned: Query<
+        (&mut Handle<TextureAtlas>, &mut TextureAtlasSprite, &mut Visibility),
+        With<TileSkin>,
+    >,
+    mut texts: Query<&mut Visibility, (With<TileText>, Without<TileSkin>)>,
+) {
+    for (points, children) in tiles.iter() {
+        if !points.is_changed() && !active.is_changed() {
+            continue;
+        }
+        let texture = active
+            .atlas
+            .as_ref()
+            .and_then(|atlas| Some((atlas, *active.tiles.get(&points.value)?)));
+        for child in children.iter() {
+            if let Ok((mut atlas, mut sprite, mut visibility)) = skinned.get_mut(*child) {
+                match texture {
+                    Some((handle, index)) => {
+                        *atlas = handle.clone();
+                        sprite.index = index;
+                        *visibility = Visibility::Inherited;
+                    }
+                    None => *visibility = Visibility::Hidden,
+                }
+            }
+            if let Ok(mut visibility) = texts.get_mut(*child) {
+                *visibility = if texture.is_some() && !active.show_text {
+                    Visibility::Hidden
+                } else {
+                    Visibility::Inherited
+                };
+            }
+        }
+    }
+}
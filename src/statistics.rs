@@ -0,0 +1,217 @@
+use crate::components::FontSpec;
+use crate::game_log::GameRecord;
+use crate::theme::ThemeColor;
+use bevy::prelude::*;
+use std::collections::BTreeMap;
+
+/// Tiles with a win rate, a game wins at every tile up to its highest one.
+const THRESHOLDS: [u32; 5] = [256, 512, 1024, 2048, 4096];
+const CHART_HEIGHT: f32 = 120.0;
+/// Bars of the score chart, longer games are sampled down to this.
+const SCORE_BARS: usize = 60;
+
+pub struct BoardSizeStats {
+    pub board_size: u8,
+    pub games: usize,
+    pub average_score: f32,
+    pub best_score: u32,
+}
+
+/// Numbers shown on the statistics page, computed from the game log.
+pub struct Statistics {
+    pub games: usize,
+    /// Share of the games reaching each threshold tile.
+    pub win_rates: Vec<(u32, f32)>,
+    pub board_sizes: Vec<BoardSizeStats>,
+    /// Number of games ending with each highest tile, lowest first.
+    pub highest_tiles: Vec<(u32, usize)>,
+    /// Score after each move of the last game.
+    pub last_scores: Vec<u32>,
+}
+
+impl Statistics {
+    pub fn new(records: &[GameRecord]) -> Self {
+        let games = records.len();
+        let win_rates = THRESHOLDS
+            .iter()
+            .map(|&tile| {
+                let wins = records.iter().filter(|record| record.highest_tile >= tile).count();
+                (tile, wins as f32 / games.max(1) as f32)
+            })
+            .collect();
+
+        let mut by_size: BTreeMap<u8, Vec<u32>> = BTreeMap::new();
+        let mut highest_tiles: BTreeMap<u32, usize> = BTreeMap::new();
+        for record in records {
+            by_size.entry(record.board_size).or_default().push(record.score);
+            *highest_tiles.entry(record.highest_tile).or_default() += 1;
+        }
+        let board_sizes = by_size
+            .into_iter()
+            .map(|(board_size, scores)| BoardSizeStats {
+                board_size,
+                games: scores.len(),
+                average_score: scores.iter().sum::<u32>() as f32 / scores.len() as f32,
+                best_score: scores.iter().copied().max().unwrap_or(0),
+            })
+            .collect();
+
+        Statistics {
+            games,
+            win_rates,
+            board_sizes,
+            highest_tiles: highest_tiles.into_iter().collect(),
+            last_scores: records.last().map(|record| record.scores.clone()).unwrap_or_default(),
+        }
+    }
+}
+
+/// Spawns the numbers on the left and the charts on the right.
+pub fn spawn_statistics(parent: &mut ChildBuilder, font_spec: &FontSpec, stats: &Statistics) {
+    let win_rates = stats
+        .win_rates
+        .iter()
+        .map(|(tile, rate)| format!("Reached {}: {:.0}%", tile, rate * 100.0))
+        .collect::<Vec<_>>()
+        .join("\n");
+    let board_sizes = stats
+        .board_sizes
+        .iter()
+        .map(|size| {
+            format!(
+                "{0}x{0}: {1} games, average {2:.0}, best {3}",
+                size.board_size, size.games, size.average_score, size.best_score
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    parent
+        .spawn(NodeBundle {
+            style: Style {
+                column_gap: Val::Px(40.0),
+                ..default()
+            },
+            ..default()
+        })
+        .with_children(|parent| {
+            parent
+                .spawn(NodeBundle {
+                    style: Style {
+                        flex_direction: FlexDirection::Column,
+                        row_gap: Val::Px(15.0),
+                        ..default()
+                    },
+                    ..default()
+                })
+                .with_children(|parent| {
+                    stats_text(parent, font_spec, format!("Games played: {}", stats.games), 20.0);
+                    stats_text(parent, font_spec, win_rates, 15.0);
+                    stats_text(parent, font_spec, board_sizes, 15.0);
+                });
+            parent
+                .spawn(NodeBundle {
+                    style: Style {
+                        flex_direction: FlexDirection::Column,
+                        row_gap: Val::Px(15.0),
+                        ..default()
+                    },
+                    ..default()
+                })
+                .with_children(|parent| {
+                    stats_text(parent, font_spec, "Highest tiles".to_string(), 15.0);
+                    spawn_tile_histogram(parent, font_spec, &stats.highest_tiles);
+                    spawn_score_chart(parent, font_spec, &stats.last_scores);
+                });
+        });
+}
+
+fn spawn_tile_histogram(parent: &mut ChildBuilder, font_spec: &FontSpec, highest_tiles: &[(u32, usize)]) {
+    let most = highest_tiles.iter().map(|(_, count)| *count).max().unwrap_or(1);
+    parent
+        .spawn(NodeBundle {
+            style: Style {
+                align_items: AlignItems::FlexEnd,
+                column_gap: Val::Px(6.0),
+                ..default()
+            },
+            ..default()
+        })
+        .with_children(|parent| {
+            for (tile, count) in highest_tiles {
+                parent
+                    .spawn(NodeBundle {
+                        style: Style {
+                            flex_direction: FlexDirection::Column,
+                            align_items: AlignItems::Center,
+                            ..default()
+                        },
+                        ..default()
+                    })
+                    .with_children(|parent| {
+                        stats_text(parent, font_spec, count.to_string(), 12.0);
+                        chart_bar(parent, 30.0, CHART_HEIGHT * *count as f32 / most as f32);
+                        stats_text(parent, font_spec, tile.to_string(), 12.0);
+                    });
+            }
+        });
+}
+
+fn spawn_score_chart(parent: &mut ChildBuilder, font_spec: &FontSpec, scores: &[u32]) {
+    let Some(&best) = scores.iter().max() else {
+        stats_text(parent, font_spec, "No score history for the last game".to_string(), 15.0);
+        return;
+    };
+    let title = format!("Last game: {} points over {} moves", best, scores.len().saturating_sub(1));
+    stats_text(parent, font_spec, title, 15.0);
+    let bars = scores.len().min(SCORE_BARS);
+    parent
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    height: Val::Px(CHART_HEIGHT),
+                    align_items: AlignItems::FlexEnd,
+                    column_gap: Val::Px(1.0),
+                    padding: UiRect::all(Val::Px(4.0)),
+                    ..default()
+                },
+                ..default()
+            },
+            ThemeColor::Board,
+        ))
+        .with_children(|parent| {
+            for bar in 0..bars {
+                let index = bar * (scores.len() - 1) / (bars - 1).max(1);
+                let height = (CHART_HEIGHT - 8.0) * scores[index] as f32 / best.max(1) as f32;
+                chart_bar(parent, 4.0, height);
+            }
+        });
+}
+
+fn chart_bar(parent: &mut ChildBuilder, width: f32, height: f32) {
+    parent.spawn((
+        NodeBundle {
+            style: Style {
+                width: Val::Px(width),
+                height: Val::Px(height.max(1.0)),
+                ..default()
+            },
+            ..default()
+        },
+        ThemeColor::ScoreBox,
+    ));
+}
+
+fn stats_text(parent: &mut ChildBuilder, font_spec: &FontSpec, text: String, font_size: f32) {
+    parent.spawn((
+        TextBundle::from_section(
+            text,
+            TextStyle {
+                font: font_spec.family.clone(),
+                font_size,
+                color: Color::WHITE,
+            },
+        ),
+        ThemeColor::Text,
+    ));
+}
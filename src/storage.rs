@@ -0,0 +1,38 @@
+use bevy::prelude::*;
+use serde::{de::DeserializeOwned, Serialize};
+use std::{
+    fs, io,
+    path::PathBuf,
+};
+
+pub fn data_dir() -> PathBuf {
+    dirs::data_local_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join("boxes")
+}
+
+/// Loads a json file from the data dir, falling back to the default
+/// when it is missing or can't be parsed.
+pub fn load_json<T: DeserializeOwned + Default>(name: &str) -> T {
+    let path = data_dir().join(name);
+    match fs::read_to_string(&path) {
+        Ok(contents) => serde_json::from_str(&contents).unwrap_or_else(|err| {
+            warn!("Ignoring {}: {}", path.display(), err);
+            T::default()
+        }),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => T::default(),
+        Err(err) => {
+            warn!("Can't read {}: {}", path.display(), err);
+            T::default()
+        }
+    }
+}
+
+pub fn save_json<T: Serialize>(name: &str, value: &T) -> io::Result<()> {
+    let path = data_dir().join(name);
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let contents = serde_json::to_string_pretty(value)?;
+    fs::write(path, contents)
+}
@@ -1,4 +1,8 @@
 use bevy::prelude::*;
+use bevy::ui::FocusPolicy;
+
+/// Dims the game under a dialog.
+pub const DIMMED: Color = Color::rgba(0.0, 0.0, 0.0, 0.6);
 
 pub fn score_container_style() -> Style {
     Style {
@@ -13,3 +17,69 @@ pub fn score_container_style() -> Style {
         ..Style::DEFAULT
     }
 }
+
+/// Node and label size of a button.
+pub struct ButtonStyle {
+    pub node: Style,
+    pub font_size: f32,
+}
+
+/// Buttons of the header above the board.
+pub fn header_button_style() -> ButtonStyle {
+    ButtonStyle {
+        node: Style {
+            min_width: Val::Px(90.0),
+            height: Val::Px(50.0),
+            padding: UiRect::horizontal(Val::Px(12.0)),
+            justify_content: JustifyContent::Center,
+            align_items: AlignItems::Center,
+            ..Style::DEFAULT
+        },
+        font_size: 20.0,
+    }
+}
+
+/// Buttons of the menus, all as wide as the column they are in.
+pub fn menu_button_style(width: f32) -> ButtonStyle {
+    ButtonStyle {
+        node: Style {
+            width: Val::Px(width),
+            justify_content: JustifyContent::Center,
+            padding: UiRect::all(Val::Px(8.0)),
+            ..Style::DEFAULT
+        },
+        font_size: 20.0,
+    }
+}
+
+/// Buttons of the dialogs, lists and the timeline.
+pub fn small_button_style() -> ButtonStyle {
+    ButtonStyle {
+        node: Style {
+            padding: UiRect::axes(Val::Px(10.0), Val::Px(5.0)),
+            justify_content: JustifyContent::Center,
+            align_items: AlignItems::Center,
+            ..Style::DEFAULT
+        },
+        font_size: 15.0,
+    }
+}
+
+/// Full screen root of an overlay, hidden until it is opened.
+pub fn overlay_root(z_index: i32) -> NodeBundle {
+    NodeBundle {
+        style: Style {
+            display: Display::None,
+            position_type: PositionType::Absolute,
+            width: Val::Percent(100.0),
+            height: Val::Percent(100.0),
+            justify_content: JustifyContent::Center,
+            align_items: AlignItems::Center,
+            ..Style::DEFAULT
+        },
+        // Keeps clicks from reaching whatever is underneath
+        focus_policy: FocusPolicy::Block,
+        z_index: ZIndex::Global(z_index),
+        ..default()
+    }
+}
@@ -0,0 +1,300 @@
+use crate::bindings::screen_closed;
+use crate::settings::{save_settings, Settings};
+use bevy::{
+    asset::{AssetLoader, FileAssetIo, LoadContext, LoadedAsset},
+    prelude::*,
+    reflect::{TypePath, TypeUuid},
+    utils::BoxedFuture,
+};
+use serde::Deserialize;
+use std::fs;
+
+const THEME_DIR: &str = "themes";
+const THEME_EXTENSION: &str = ".theme.ron";
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub struct TileColors {
+    pub tile: Color,
+    pub text: Color,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub struct ButtonColors {
+    pub normal: Color,
+    pub hovered: Color,
+    pub pressed: Color,
+    pub text: Color,
+}
+
+/// Colors of everything on screen, loaded from `assets/themes/<name>.theme.ron`.
+/// The resource holds the active theme, the built-in default until the file loaded.
+#[derive(Debug, Clone, Deserialize, Resource, TypeUuid, TypePath)]
+#[uuid = "6f0b3c1e-52a4-4d8e-9c1b-7e2f4a9d3b61"]
+pub struct Theme {
+    pub background: Color,
+    pub board: Color,
+    pub empty_cell: Color,
+    pub score_box: Color,
+    pub text: Color,
+    /// Tile and text color for 2, 4, 8, ...
+    pub tiles: Vec<TileColors>,
+    /// Used for values past the end of `tiles`.
+    pub high_tile: TileColors,
+    pub button: ButtonColors,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        let dark_text = Color::lcha(0.25, 0.2, 281.0, 1.0);
+        let tile = |lightness, chroma, hue, text| TileColors {
+            tile: Color::lcha(lightness, chroma, hue, 1.0),
+            text,
+        };
+        Theme {
+            background: Color::rgb(0.122, 0.149, 0.22),
+            board: Color::lcha(0.06, 0.088, 281.0, 1.0),
+            empty_cell: Color::lcha(0.55, 0.5, 315.0, 1.0),
+            score_box: Color::lcha(0.55, 0.5, 315.0, 1.0),
+            text: Color::WHITE,
+            tiles: vec![
+                tile(0.90, 0.10, 315.0, dark_text),
+                tile(0.85, 0.20, 315.0, dark_text),
+                tile(0.75, 0.45, 340.0, Color::WHITE),
+                tile(0.68, 0.55, 355.0, Color::WHITE),
+                tile(0.62, 0.65, 10.0, Color::WHITE),
+                tile(0.58, 0.75, 25.0, Color::WHITE),
+                tile(0.85, 0.55, 60.0, dark_text),
+                tile(0.82, 0.65, 70.0, dark_text),
+                tile(0.78, 0.75, 80.0, Color::WHITE),
+                tile(0.74, 0.85, 90.0, Color::WHITE),
+                tile(0.70, 0.95, 100.0, Color::WHITE),
+            ],
+            high_tile: tile(0.2, 0.3, 281.0, Color::WHITE),
+            button: ButtonColors {
+                normal: Color::lcha(0.15, 0.5, 281.0, 1.0),
+                hovered: Color::lcha(0.55, 0.5, 281.0, 1.0),
+                pressed: Color::lcha(0.75, 0.5, 281.0, 1.0),
+                text: Color::WHITE,
+            },
+        }
+    }
+}
+
+impl Theme {
+    /// Tile and text color of a tile showing `value`.
+    pub fn tile(&self, value: u32) -> TileColors {
+        let index = value.max(2).ilog2() as usize - 1;
+        self.tiles.get(index).copied().unwrap_or(self.high_tile)
+    }
+
+    pub fn color(&self, themed: ThemeColor) -> Color {
+        match themed {
+            ThemeColor::Background => self.background,
+            ThemeColor::Board => self.board,
+            ThemeColor::EmptyCell => self.empty_cell,
+            ThemeColor::ScoreBox => self.score_box,
+            ThemeColor::Text => self.text,
+            ThemeColor::Button => self.button.normal,
+            ThemeColor::ButtonText => self.button.text,
+        }
+    }
+
+    /// Background of a button in the given state.
+    pub fn button_color(&self, interaction: Interaction) -> BackgroundColor {
+        match interaction {
+            Interaction::Pressed => self.button.pressed.into(),
+            Interaction::Hovered => self.button.hovered.into(),
+            Interaction::None => self.button.normal.into(),
+        }
+    }
+}
+
+/// Which theme color a sprite, node or text takes, so it follows theme changes.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ThemeColor {
+    Background,
+    Board,
+    EmptyCell,
+    ScoreBox,
+    Text,
+    Button,
+    ButtonText,
+}
+
+#[derive(Default)]
+pub struct ThemeLoader;
+
+impl AssetLoader for ThemeLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), bevy::asset::Error>> {
+        Box::pin(async move {
+            let theme = ron::de::from_bytes::<Theme>(bytes)?;
+            if theme.tiles.is_empty() {
+                return Err(bevy::asset::Error::msg("a theme needs at least one tile color"));
+            }
+            load_context.set_default_asset(LoadedAsset::new(theme));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["theme.ron"]
+    }
+}
+
+#[derive(Resource)]
+pub struct Themes {
+    /// Name of the active theme, its file name without the extension.
+    pub active: String,
+    pub available: Vec<(String, Handle<Theme>)>,
+}
+
+impl Default for Themes {
+    fn default() -> Self {
+        Themes {
+            active: "default".to_string(),
+            available: Vec::new(),
+        }
+    }
+}
+
+impl Themes {
+    fn handle(&self, name: &str) -> Option<&Handle<Theme>> {
+        self.available
+            .iter()
+            .find(|(available, _)| available == name)
+            .map(|(_, handle)| handle)
+    }
+
+    /// Makes the theme after the active one active, wrapping around.
+    pub fn cycle(&mut self) {
+        let index = self
+            .available
+            .iter()
+            .position(|(name, _)| *name == self.active)
+            .map_or(0, |index| (index + 1) % self.available.len().max(1));
+        if let Some((name, _)) = self.available.get(index) {
+            self.active = name.clone();
+        }
+    }
+}
+
+/// Names of the theme files in assets/themes, read from disk so
+/// command line options can be checked before the assets load.
+pub fn theme_names() -> Vec<String> {
+    let dir = FileAssetIo::get_base_path().join("assets").join(THEME_DIR);
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut names: Vec<String> = entries
+        .filter_map(|entry| {
+            let name = entry.ok()?.file_name().into_string().ok()?;
+            Some(name.strip_suffix(THEME_EXTENSION)?.to_string())
+        })
+        .collect();
+    names.sort();
+    names
+}
+
+pub struct ThemePlugin;
+impl Plugin for ThemePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_asset::<Theme>()
+            .init_asset_loader::<ThemeLoader>()
+            .init_resource::<Theme>()
+            .init_resource::<Themes>()
+            .add_systems(Startup, load_themes)
+            .add_systems(Update, (cycle_theme.run_if(screen_closed), use_active_theme).chain())
+            .add_systems(PostUpdate, apply_theme_colors);
+    }
+}
+
+fn load_themes(asset_server: Res<AssetServer>, mut themes: ResMut<Themes>) {
+    let handles = asset_server.load_folder(THEME_DIR).unwrap_or_else(|err| {
+        error!("Can't load the themes: {:?}", err);
+        Vec::new()
+    });
+    for handle in handles {
+        let Some(path) = asset_server.get_handle_path(&handle) else {
+            continue;
+        };
+        let file_name = path.path().file_name().and_then(|name| name.to_str());
+        if let Some(name) = file_name.and_then(|name| name.strip_suffix(THEME_EXTENSION)) {
+            themes.available.push((name.to_string(), handle.typed()));
+        }
+    }
+    themes.available.sort_by(|a, b| a.0.cmp(&b.0));
+    if themes.handle(&themes.active).is_none() {
+        warn!("No theme named {}, using the default", themes.active);
+        themes.active = "default".to_string();
+    }
+}
+
+// Picked like on the settings screen, so it is kept for the next start.
+fn cycle_theme(input: Res<Input<KeyCode>>, mut themes: ResMut<Themes>, mut settings: ResMut<Settings>) {
+    if input.just_pressed(KeyCode::F2) {
+        themes.cycle();
+        info!("Theme: {}", themes.active);
+        settings.theme = themes.active.clone();
+        save_settings(&settings);
+    }
+}
+
+// Copies the active theme into the resource when it is picked,
+// loaded or changed on disk.
+fn use_active_theme(
+    mut theme_events: EventReader<AssetEvent<Theme>>,
+    themes: Res<Themes>,
+    theme_assets: Res<Assets<Theme>>,
+    mut theme: ResMut<Theme>,
+) {
+    let Some(handle) = themes.handle(&themes.active) else {
+        return;
+    };
+    let reloaded = theme_events.iter().any(|event| match event {
+        AssetEvent::Created { handle: changed } | AssetEvent::Modified { handle: changed } => {
+            changed == handle
+        }
+        AssetEvent::Removed { .. } => false,
+    });
+    if themes.is_changed() || reloaded {
+        if let Some(active) = theme_assets.get(handle) {
+            *theme = active.clone();
+        }
+    }
+}
+
+fn apply_theme_colors(
+    theme: Res<Theme>,
+    mut clear_color: ResMut<ClearColor>,
+    mut themed: Query<(
+        Ref<ThemeColor>,
+        Option<&mut Sprite>,
+        Option<&mut BackgroundColor>,
+        Option<&mut Text>,
+    )>,
+) {
+    if theme.is_changed() {
+        clear_color.0 = theme.background;
+    }
+    for (themed, sprite, background, text) in themed.iter_mut() {
+        if !theme.is_changed() && !themed.is_added() {
+            continue;
+        }
+        let color = theme.color(*themed);
+        if let Some(mut sprite) = sprite {
+            sprite.color = color;
+        }
+        if let Some(mut background) = background {
+            background.0 = color;
+        }
+        if let Some(mut text) = text {
+            for section in text.sections.iter_mut() {
+                section.style.color = color;
+            }
+        }
+    }
+}
@@ -0,0 +1,359 @@
+use crate::components::{
+    Board, FontSpec, Game, GameRng, Points, Position, RunState, SpawnQueue,
+};
+use crate::saves::ReplayPlayer;
+use crate::settings::Settings;
+use crate::styles::small_button_style;
+use crate::theme::{Theme, ThemeColor};
+use crate::ui::spawn_button;
+use crate::utility::{spawn_tile, NewGameEvent};
+use bevy::prelude::*;
+use bevy::ui::RelativeCursorPosition;
+use rand::rngs::StdRng;
+
+/// The board as it was after one move, including the rng
+/// so that resuming from it spawns the same tiles again.
+#[derive(Clone)]
+pub struct Snapshot {
+    pub tiles: Vec<(Position, Points)>,
+    pub score: u32,
+    pub moves: u32,
+    pub rng: StdRng,
+    pub spawn_queue: SpawnQueue,
+}
+
+#[derive(Default, Resource)]
+pub struct Timeline {
+    pub snapshots: Vec<Snapshot>,
+    /// Snapshot shown while scrubbing, `None` while the game is live.
+    pub cursor: Option<usize>,
+}
+
+impl Timeline {
+    pub fn is_live(&self) -> bool {
+        self.cursor.is_none()
+    }
+
+    pub fn last_index(&self) -> usize {
+        self.snapshots.len().saturating_sub(1)
+    }
+
+    pub fn displayed(&self) -> usize {
+        self.cursor.unwrap_or(self.last_index())
+    }
+
+    pub fn scrub_to(&mut self, index: usize) {
+        let index = index.min(self.last_index());
+        self.cursor = if index == self.last_index() {
+            None
+        } else {
+            Some(index)
+        };
+    }
+
+    /// Drops every snapshot after the scrubbed one and makes it live.
+    pub fn play_from_here(&mut self) {
+        if let Some(index) = self.cursor.take() {
+            self.snapshots.truncate(index + 1);
+        }
+    }
+
+    /// Drops the latest snapshot, returning the one to go back to.
+    pub fn undo(&mut self) -> Option<&Snapshot> {
+        if !self.can_undo() {
+            return None;
+        }
+        self.snapshots.pop();
+        self.snapshots.last()
+    }
+
+    /// Whether there is a move before the live one to go back to.
+    pub fn can_undo(&self) -> bool {
+        self.is_live() && self.snapshots.len() >= 2
+    }
+}
+
+#[derive(Event)]
+pub struct UndoEvent;
+
+#[derive(Component)]
+pub struct TimelineSlider;
+
+#[derive(Component)]
+pub struct TimelineHandle;
+
+#[derive(Component)]
+pub struct TimelineLabel;
+
+#[derive(Component)]
+pub struct PlayFromHereButton;
+
+pub struct TimelinePlugin;
+impl Plugin for TimelinePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Timeline>()
+            .add_event::<UndoEvent>()
+            .add_systems(Startup, setup_timeline_ui)
+            .add_systems(PreUpdate, clear_timeline.run_if(on_event::<NewGameEvent>()))
+            .add_systems(
+                Update,
+                (
+                    (scrub_slider, show_snapshot)
+                        .chain()
+                        .run_if(in_state(RunState::Playing)),
+                    // Also undoes the move that ended the game
+                    undo_move,
+                    (timeline_buttons, render_timeline)
+                        .chain()
+                        .run_if(in_state(RunState::Playing)),
+                )
+                    .chain(),
+            )
+            .add_systems(
+                PostUpdate,
+                record_snapshot
+                    .run_if(in_state(RunState::Playing))
+                    .run_if(is_live),
+            );
+    }
+}
+
+pub fn is_live(timeline: Res<Timeline>) -> bool {
+    timeline.is_live()
+}
+
+fn clear_timeline(mut timeline: ResMut<Timeline>) {
+    *timeline = Timeline::default();
+}
+
+// Runs after the frame's commands were applied, so spawned
+// tiles and despawned merges are part of the snapshot.
+fn record_snapshot(
+    mut timeline: ResMut<Timeline>,
+    game: Res<Game>,
+    tiles: Query<(&Position, &Points)>,
+    rng: Res<GameRng>,
+    spawn_queue: Res<SpawnQueue>,
+) {
+    let recorded = timeline.snapshots.last().map(|snapshot| snapshot.moves);
+    if recorded == Some(game.moves) || tiles.is_empty() {
+        return;
+    }
+    timeline.snapshots.push(Snapshot {
+        tiles: tiles.iter().map(|(pos, points)| (*pos, *points)).collect(),
+        score: game.score,
+        moves: game.moves,
+        rng: rng.0.clone(),
+        spawn_queue: spawn_queue.clone(),
+    });
+}
+
+pub fn restore_snapshot(
+    commands: &mut Commands,
+    tiles: &Query<Entity, With<Position>>,
+    board: &Board,
+    font_spec: &Res<FontSpec>,
+    snapshot: &Snapshot,
+) {
+    for entity in tiles.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    for (pos, points) in snapshot.tiles.iter() {
+        spawn_tile(commands, board, font_spec, *pos, *points);
+    }
+}
+
+fn scrub_slider(
+    mut timeline: ResMut<Timeline>,
+    slider: Query<(&Interaction, &RelativeCursorPosition), With<TimelineSlider>>,
+) {
+    let (interaction, cursor) = slider.single();
+    if *interaction != Interaction::Pressed {
+        return;
+    }
+    if let Some(normalized) = cursor.normalized {
+        let index = (normalized.x.clamp(0.0, 1.0) * timeline.last_index() as f32).round() as usize;
+        if index != timeline.displayed() {
+            timeline.scrub_to(index);
+        }
+    }
+}
+
+fn show_snapshot(
+    mut commands: Commands,
+    timeline: Res<Timeline>,
+    mut shown: Local<Option<usize>>,
+    tiles: Query<Entity, With<Position>>,
+    query_board: Query<&Board>,
+    font_spec: Res<FontSpec>,
+    mut game: ResMut<Game>,
+    mut rng: ResMut<GameRng>,
+    mut spawn_queue: ResMut<SpawnQueue>,
+) {
+    if *shown == timeline.cursor {
+        return;
+    }
+    *shown = timeline.cursor;
+    if let Some(snapshot) = timeline.snapshots.get(timeline.displayed()) {
+        restore_snapshot(&mut commands, &tiles, query_board.single(), &font_spec, snapshot);
+        game.score = snapshot.score;
+        game.moves = snapshot.moves;
+        rng.0 = snapshot.rng.clone();
+        *spawn_queue = snapshot.spawn_queue.clone();
+        // Back to live, possibly after dropping the moves past the cursor
+        if timeline.is_live() {
+            let kept = snapshot.moves - timeline.snapshots[0].moves;
+            game.shifts.truncate(kept as usize);
+        }
+    }
+}
+
+fn undo_move(
+    mut commands: Commands,
+    mut undo_reader: EventReader<UndoEvent>,
+    mut timeline: ResMut<Timeline>,
+    tiles: Query<Entity, With<Position>>,
+    query_board: Query<&Board>,
+    font_spec: Res<FontSpec>,
+    mut game: ResMut<Game>,
+    mut rng: ResMut<GameRng>,
+    mut spawn_queue: ResMut<SpawnQueue>,
+    run_state: Res<State<RunState>>,
+    mut next_state: ResMut<NextState<RunState>>,
+    settings: Res<Settings>,
+    player: Res<ReplayPlayer>,
+) {
+    // Several undo requests in one frame still only go back one move,
+    // the despawns of the first restore aren't applied yet.
+    // A replay plays its shifts as they were.
+    if undo_reader.iter().count() == 0 || player.playing || !settings.can_undo(game.undos) {
+        return;
+    }
+    let first_moves = timeline.snapshots.first().map_or(0, |snapshot| snapshot.moves);
+    if let Some(snapshot) = timeline.undo() {
+        restore_snapshot(&mut commands, &tiles, query_board.single(), &font_spec, snapshot);
+        game.score = snapshot.score;
+        game.moves = snapshot.moves;
+        game.shifts.truncate((snapshot.moves - first_moves) as usize);
+        game.undos += 1;
+        rng.0 = snapshot.rng.clone();
+        *spawn_queue = snapshot.spawn_queue.clone();
+        if *run_state.get() == RunState::GameOver {
+            next_state.set(RunState::Playing);
+        }
+    }
+}
+
+fn timeline_buttons(
+    mut interaction_query: Query<
+        (&Interaction, &mut BackgroundColor),
+        (Changed<Interaction>, With<PlayFromHereButton>),
+    >,
+    mut timeline: ResMut<Timeline>,
+    theme: Res<Theme>,
+) {
+    for (interaction, mut color) in interaction_query.iter_mut() {
+        *color = theme.button_color(*interaction);
+        match interaction {
+            Interaction::Pressed => timeline.play_from_here(),
+            Interaction::Hovered | Interaction::None => {}
+        }
+    }
+}
+
+fn render_timeline(
+    timeline: Res<Timeline>,
+    mut handle_query: Query<&mut Style, (With<TimelineHandle>, Without<PlayFromHereButton>)>,
+    mut play_query: Query<&mut Style, (With<PlayFromHereButton>, Without<TimelineHandle>)>,
+    mut label_query: Query<&mut Text, With<TimelineLabel>>,
+) {
+    if !timeline.is_changed() {
+        return;
+    }
+    let progress = if timeline.last_index() == 0 {
+        1.0
+    } else {
+        timeline.displayed() as f32 / timeline.last_index() as f32
+    };
+    handle_query.single_mut().left = Val::Percent(progress * 100.0);
+    play_query.single_mut().display = if timeline.is_live() {
+        Display::None
+    } else {
+        Display::Flex
+    };
+    label_query.single_mut().sections[0].value =
+        format!("{} / {}", timeline.displayed(), timeline.last_index());
+}
+
+fn setup_timeline_ui(mut commands: Commands, font_spec: Res<FontSpec>) {
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                bottom: Val::Px(20.0),
+                width: Val::Percent(100.0),
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                column_gap: Val::Px(15.0),
+                ..default()
+            },
+            ..default()
+        })
+        .with_children(|parent| {
+            // Slider track, the handle is positioned in percent of it
+            parent
+                .spawn((
+                    NodeBundle {
+                        style: Style {
+                            width: Val::Px(300.0),
+                            height: Val::Px(10.0),
+                            ..default()
+                        },
+                        ..default()
+                    },
+                    Interaction::default(),
+                    ThemeColor::EmptyCell,
+                    RelativeCursorPosition::default(),
+                    TimelineSlider,
+                ))
+                .with_children(|parent| {
+                    parent.spawn((
+                        NodeBundle {
+                            style: Style {
+                                position_type: PositionType::Absolute,
+                                width: Val::Px(10.0),
+                                height: Val::Px(20.0),
+                                top: Val::Px(-5.0),
+                                margin: UiRect::left(Val::Px(-5.0)),
+                                left: Val::Percent(100.0),
+                                ..default()
+                            },
+                            ..default()
+                        },
+                        ThemeColor::ScoreBox,
+                        TimelineHandle,
+                    ));
+                });
+
+            parent.spawn((
+                TextBundle::from_section(
+                    "0 / 0",
+                    TextStyle {
+                        font: font_spec.family.clone(),
+                        font_size: 15.0,
+                        color: Color::WHITE,
+                    },
+                ),
+                ThemeColor::Text,
+                TimelineLabel,
+            ));
+
+            spawn_button(
+                parent,
+                &font_spec,
+                "Play from here",
+                PlayFromHereButton,
+                small_button_style(),
+            );
+        });
+}
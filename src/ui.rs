@@ -1,83 +1,262 @@
-use crate::colors;
-use crate::components::{FontSpec, Game, RunState};
-use crate::styles::score_container_style;
+use crate::animation::{save_animation_settings, AnimationSettings, Easing};
+use crate::bindings::{screen_closed, BindingScreen};
+use crate::components::{Board, FontSpec, Game, GameMode, NextGame, Points, Position, RunState};
+use crate::confirm::{Abandon, AbandonRequest};
+use crate::menu::{toggle_menu, Menu};
+use crate::settings::{
+    load_settings, save_settings, Settings, BOARD_SIZES, FOUR_CHANCES, MOVE_QUEUE_LENGTHS, UNDO_LIMITS,
+};
+use crate::timeline::UndoEvent;
+use crate::utility::{BoardShift, MoveQueue, NewGameEvent};
+use crate::styles::{
+    header_button_style, overlay_root, score_container_style, small_button_style, ButtonStyle,
+};
+use crate::theme::{Theme, ThemeColor, Themes};
+use bevy::audio::GlobalVolume;
 use bevy::prelude::*;
 #[derive(Component)]
 pub struct ScoreDisplay;
 
 #[derive(Component)]
 pub struct BestScoreDisplay;
+
+#[derive(Default, Resource)]
+pub struct SettingsScreen {
+    pub open: bool,
+    pub message: String,
+}
+
+pub fn settings_closed(screen: Res<SettingsScreen>) -> bool {
+    !screen.open
+}
+
+#[derive(Component)]
+pub struct SettingsScreenRoot;
+
+#[derive(Component)]
+pub struct SettingsList;
+
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SettingsButton {
+    BoardSize,
+    FourChance,
+    AnimationSpeed,
+    Easing,
+    ReducedMotion,
+    Theme,
+    KeyBindings,
+    Volume,
+    UndoLimit,
+    ConfirmAbandon,
+    MoveQueue,
+    Defaults,
+    Close,
+}
+
+/// What a header button does when pressed, each button has its own label.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ButtonAction {
+    NewGame,
+    EndGame,
+    Undo,
+    Hint,
+    Pause,
+}
+
+impl ButtonAction {
+    pub const ALL: [ButtonAction; 5] = [
+        ButtonAction::Undo,
+        ButtonAction::Hint,
+        ButtonAction::Pause,
+        ButtonAction::EndGame,
+        ButtonAction::NewGame,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            ButtonAction::NewGame => "New Game",
+            ButtonAction::EndGame => "End Game",
+            ButtonAction::Undo => "Undo",
+            ButtonAction::Hint => "Hint",
+            ButtonAction::Pause => "Pause",
+        }
+    }
+
+    /// Buttons are hidden in the states they do nothing in.
+    fn shown(&self, run_state: RunState) -> bool {
+        match self {
+            ButtonAction::NewGame => run_state == RunState::GameOver,
+            ButtonAction::EndGame => matches!(run_state, RunState::Playing | RunState::Paused),
+            ButtonAction::Undo => matches!(run_state, RunState::Playing | RunState::GameOver),
+            ButtonAction::Hint | ButtonAction::Pause => run_state == RunState::Playing,
+        }
+    }
+}
+
+/// Shift suggested by the hint button, good until the board changes.
+#[derive(Resource, Default)]
+pub struct Hint {
+    pub shift: Option<BoardShift>,
+    /// Moves and score of the game the hint was given for.
+    pub given_at: Option<(u32, u32)>,
+}
+
+impl Hint {
+    fn current(&self, game: &Game) -> Option<Option<BoardShift>> {
+        (self.given_at == Some((game.moves, game.score))).then_some(self.shift)
+    }
+}
+
 pub struct GameUiPlugin;
 impl Plugin for GameUiPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(Startup, setup_ui)
+        // Loaded right away, the command line options are applied on top
+        let (settings, problems) = load_settings();
+        let message = if problems.is_empty() {
+            String::new()
+        } else {
+            format!("Reset to the defaults: {}", problems.join(", "))
+        };
+        app.insert_resource(GlobalVolume::new(volume_level(&settings)))
+            .insert_resource(MoveQueue {
+                max_len: settings.move_queue,
+                ..default()
+            })
+            .insert_resource(settings)
+            .insert_resource(SettingsScreen { open: false, message })
+            .init_resource::<Hint>()
+            .add_systems(Startup, (setup_ui, setup_settings_screen))
+            .add_systems(
+                PreUpdate,
+                close_settings_screen
+                    .run_if(screen_closed)
+                    .after(bevy::input::InputSystem)
+                    .before(toggle_menu),
+            )
             .add_systems(Update, 
                 (
                 scoreboard, 
                 button_interaction_system,
-                button_text_system
+                button_text_system,
+                (settings_buttons, render_settings_screen).chain(),
                 )
             );
     }
 }
 
+fn volume_level(settings: &Settings) -> f32 {
+    f32::from(settings.volume) / 100.0
+}
+
 fn scoreboard(
     game: Res<Game>, 
+    time: Res<Time>,
+    settings: Res<AnimationSettings>,
+    mut shown_score: Local<f32>,
     mut query_score: Query<&mut Text, 
     (With<ScoreDisplay>, Without<BestScoreDisplay>)>,
     mut query_best_score: Query<&mut Text, 
     (With<BestScoreDisplay>, Without<ScoreDisplay>)>
 ) {
+    let score = game.score as f32;
+    if !settings.flourishes() || score < *shown_score {
+        *shown_score = score;
+    } else if *shown_score < score {
+        // Closes a share of the gap each frame, at least a point
+        let share = (time.delta_seconds() / settings.score_count().as_secs_f32()).min(1.0);
+        let step = ((score - *shown_score) * share).max(1.0);
+        *shown_score = (*shown_score + step).min(score);
+    }
     let mut text = query_score.single_mut();
-    text.sections[0].value = game.score.to_string();
+    text.sections[0].value = (*shown_score as u32).to_string();
 
     let mut text = query_best_score.single_mut();
     text.sections[0].value = game.best_score.to_string();
 }
+
 fn button_interaction_system(
     mut interaction_query: Query<
-        (&Interaction, &mut BackgroundColor),
-        (Changed<Interaction>, With<Button>),
+        (&Interaction, &mut BackgroundColor, &ButtonAction),
+        Changed<Interaction>,
     >,
-    run_state: Res<State<RunState>>,
+    mut abandon_writer: EventWriter<AbandonRequest>,
+    mut new_game_writer: EventWriter<NewGameEvent>,
+    mut undo_writer: EventWriter<UndoEvent>,
     mut next_state: ResMut<NextState<RunState>>,
+    mut game: ResMut<Game>,
+    mut hint: ResMut<Hint>,
+    tiles: Query<(&Position, &Points)>,
+    query_board: Query<&Board>,
+    theme: Res<Theme>,
 ) {
-    for (interaction, mut color) in interaction_query.iter_mut() {
-        match interaction {
-            Interaction::Pressed => {
-                *color = colors::button::PRESSED.into();
-                match run_state.get() {
-                    RunState::Playing => {
-                        next_state.set(RunState::GameOver);
-                    }
-                    RunState::GameOver => next_state.set(RunState::Playing),
+    for (interaction, mut color, action) in interaction_query.iter_mut() {
+        *color = theme.button_color(*interaction);
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+        match action {
+            ButtonAction::NewGame => new_game_writer.send(NewGameEvent),
+            ButtonAction::EndGame => abandon_writer.send(AbandonRequest(Abandon::EndGame)),
+            ButtonAction::Undo => undo_writer.send(UndoEvent),
+            ButtonAction::Pause => next_state.set(RunState::Paused),
+            ButtonAction::Hint => {
+                if hint.current(&game).is_some() {
+                    continue;
                 }
+                let board_size = query_board.single().size;
+                let tiles: Vec<(Position, u32)> = tiles
+                    .iter()
+                    .map(|(position, points)| (*position, points.value))
+                    .collect();
+                // The shift scoring the most, the first one listed on a tie
+                hint.shift = BoardShift::ALL
+                    .iter()
+                    .filter_map(|shift| Some((*shift, shift.points(board_size, &tiles)?)))
+                    .fold(None, |best: Option<(BoardShift, u32)>, (shift, points)| match best {
+                        Some((_, best_points)) if best_points >= points => best,
+                        _ => Some((shift, points)),
+                    })
+                    .map(|(shift, _)| shift);
+                hint.given_at = Some((game.moves, game.score));
+                game.hints += 1;
             }
-            Interaction::Hovered => *color = colors::button::HOVERED.into(),
-            Interaction::None => *color = colors::button::NORMAL.into(),
         }
     }
 }
 
 fn button_text_system(
-    button_query: Query<&Children, With<Button>>,
+    mut button_query: Query<(&ButtonAction, &Children, &mut Style)>,
     mut text_query: Query<&mut Text>,
     run_state: Res<State<RunState>>,
+    game: Res<Game>,
+    hint: Res<Hint>,
 ) {
-    let children = button_query.single();
-    let first_child_entity = children
-        .first()
-        .expect("expect button to have a first child");
-    let mut text = text_query.get_mut(*first_child_entity).unwrap();
-    match run_state.get() {
-        RunState::Playing =>  {
-            text.sections[0].value = "End Game".to_string();
-        },
-        RunState::GameOver => {
-            text.sections[0].value = "New Game".to_string();
+    if !run_state.is_changed() && !game.is_changed() && !hint.is_changed() {
+        return;
+    }
+    for (action, children, mut style) in button_query.iter_mut() {
+        style.display = if action.shown(*run_state.get()) {
+            Display::Flex
+        } else {
+            Display::None
+        };
+        let label = match (action, hint.current(&game)) {
+            (ButtonAction::Hint, Some(Some(shift))) => format!("Try {:?}", shift),
+            (ButtonAction::Hint, Some(None)) => "No moves".to_string(),
+            _ => action.label().to_string(),
+        };
+        let Some(mut text) = children
+            .first()
+            .and_then(|child| text_query.get_mut(*child).ok())
+        else {
+            continue;
+        };
+        if text.sections[0].value != label {
+            text.sections[0].value = label;
         }
     }
 }
+
 fn setup_ui(mut commands: Commands, font_spec: Res<FontSpec>) {
     commands
         .spawn(NodeBundle {
@@ -92,13 +271,16 @@ fn setup_ui(mut commands: Commands, font_spec: Res<FontSpec>) {
             ..default()
         })
         .with_children(|parent| {
-            parent.spawn(TextBundle::from_section(
-                "2048",
-                TextStyle {
-                    font: font_spec.family.clone(),
-                    font_size: 40.0,
-                    color: Color::WHITE,
-                },
+            parent.spawn((
+                TextBundle::from_section(
+                    "2048",
+                    TextStyle {
+                        font: font_spec.family.clone(),
+                        font_size: 40.0,
+                        color: Color::WHITE,
+                    },
+                ),
+                ThemeColor::Text,
             ));
 
             // div
@@ -117,13 +299,15 @@ fn setup_ui(mut commands: Commands, font_spec: Res<FontSpec>) {
                 .with_children(|parent| {
                     // Score box
                     parent
-                        .spawn(NodeBundle {
-                            style: score_container_style(),
-                            background_color: BackgroundColor(colors::SCORE_BOX),
-                            ..default()
-                        })
+                        .spawn((
+                            NodeBundle {
+                                style: score_container_style(),
+                                ..default()
+                            },
+                            ThemeColor::ScoreBox,
+                        ))
                         .with_children(|parent| {
-                            parent.spawn(
+                            parent.spawn((
                                 TextBundle::from_section(
                                     "Score",
                                     TextStyle {
@@ -133,7 +317,8 @@ fn setup_ui(mut commands: Commands, font_spec: Res<FontSpec>) {
                                     },
                                 )
                                 .with_text_alignment(TextAlignment::Center),
-                            );
+                                ThemeColor::Text,
+                            ));
                             parent.spawn((
                                 TextBundle::from_section(
                                     "<score>",
@@ -144,6 +329,7 @@ fn setup_ui(mut commands: Commands, font_spec: Res<FontSpec>) {
                                     },
                                 )
                                 .with_text_alignment(TextAlignment::Center),
+                                ThemeColor::Text,
                                 ScoreDisplay,
                             ));
                         });
@@ -151,13 +337,15 @@ fn setup_ui(mut commands: Commands, font_spec: Res<FontSpec>) {
 
                     // Best score box
                     parent
-                        .spawn(NodeBundle {
-                            style: score_container_style(),
-                            background_color: BackgroundColor(colors::SCORE_BOX),
-                            ..default()
-                        })
+                        .spawn((
+                            NodeBundle {
+                                style: score_container_style(),
+                                ..default()
+                            },
+                            ThemeColor::ScoreBox,
+                        ))
                         .with_children(|parent| {
-                            parent.spawn(
+                            parent.spawn((
                                 TextBundle::from_section(
                                     "Best",
                                     TextStyle {
@@ -167,7 +355,8 @@ fn setup_ui(mut commands: Commands, font_spec: Res<FontSpec>) {
                                     },
                                 )
                                 .with_text_alignment(TextAlignment::Center),
-                            );
+                                ThemeColor::Text,
+                            ));
                             parent.spawn((
                                 TextBundle::from_section(
                                     "<score>",
@@ -178,36 +367,299 @@ fn setup_ui(mut commands: Commands, font_spec: Res<FontSpec>) {
                                     },
                                 )
                                 .with_text_alignment(TextAlignment::Center),
+                                ThemeColor::Text,
                                 BestScoreDisplay,
                             ));
                         });
                     // end of best score box
                 });
             // end of div
-            // Button
+            // Buttons
             parent
-                .spawn(ButtonBundle {
+                .spawn(NodeBundle {
                     style: Style {
-                        width: Val::Px(130.0),
-                        height: Val::Px(50.0),
-                        justify_content: JustifyContent::Center,
-                        align_items: AlignItems::Center,
+                        column_gap: Val::Px(10.0),
                         ..default()
                     },
                     ..default()
                 })
                 .with_children(|parent| {
-                    parent.spawn(TextBundle {
-                        text: Text::from_section(
-                            "Button",
+                    for action in ButtonAction::ALL {
+                        spawn_button(
+                            parent,
+                            &font_spec,
+                            action.label(),
+                            action,
+                            header_button_style(),
+                        );
+                    }
+                });
+        });
+}
+
+// Takes the Escape press, so it doesn't also toggle the menu.
+fn close_settings_screen(mut input: ResMut<Input<KeyCode>>, mut screen: ResMut<SettingsScreen>) {
+    if screen.open && input.clear_just_pressed(KeyCode::Escape) {
+        screen.open = false;
+    }
+}
+
+fn settings_buttons(
+    mut interaction_query: Query<
+        (&Interaction, &mut BackgroundColor, &SettingsButton),
+        Changed<Interaction>,
+    >,
+    theme: Res<Theme>,
+    mut screen: ResMut<SettingsScreen>,
+    mut binding_screen: ResMut<BindingScreen>,
+    mut settings: ResMut<Settings>,
+    mut animation_settings: ResMut<AnimationSettings>,
+    mut themes: ResMut<Themes>,
+    mut next_game: ResMut<NextGame>,
+    mut menu: ResMut<Menu>,
+    mut global_volume: ResMut<GlobalVolume>,
+    mut move_queue: ResMut<MoveQueue>,
+) {
+    for (interaction, mut color, button) in interaction_query.iter_mut() {
+        *color = theme.button_color(*interaction);
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+        match button {
+            SettingsButton::BoardSize => {
+                settings.board_size = if settings.board_size >= *BOARD_SIZES.end() {
+                    *BOARD_SIZES.start()
+                } else {
+                    settings.board_size + 1
+                };
+            }
+            SettingsButton::FourChance => {
+                let index = FOUR_CHANCES
+                    .iter()
+                    .position(|chance| *chance == settings.four_chance)
+                    .map_or(0, |index| (index + 1) % FOUR_CHANCES.len());
+                settings.four_chance = FOUR_CHANCES[index];
+            }
+            // Kept in animation.json with the durations
+            SettingsButton::AnimationSpeed => {
+                animation_settings.speed = animation_settings.speed.next();
+                save_animation_settings(&animation_settings);
+                continue;
+            }
+            SettingsButton::Easing => {
+                let index = Easing::ALL
+                    .iter()
+                    .position(|easing| *easing == animation_settings.easing)
+                    .map_or(0, |index| (index + 1) % Easing::ALL.len());
+                animation_settings.easing = Easing::ALL[index];
+                save_animation_settings(&animation_settings);
+                continue;
+            }
+            SettingsButton::ReducedMotion => {
+                animation_settings.reduced_motion = !animation_settings.reduced_motion;
+                save_animation_settings(&animation_settings);
+                continue;
+            }
+            SettingsButton::Theme => {
+                themes.cycle();
+                settings.theme = themes.active.clone();
+            }
+            SettingsButton::KeyBindings => {
+                binding_screen.open = true;
+                continue;
+            }
+            SettingsButton::Volume => {
+                settings.volume = if settings.volume >= 100 {
+                    0
+                } else {
+                    (settings.volume / 10 + 1) * 10
+                };
+            }
+            SettingsButton::UndoLimit => {
+                let index = UNDO_LIMITS
+                    .iter()
+                    .position(|limit| *limit == settings.undo_limit)
+                    .map_or(0, |index| (index + 1) % UNDO_LIMITS.len());
+                settings.undo_limit = UNDO_LIMITS[index];
+            }
+            SettingsButton::ConfirmAbandon => settings.confirm_abandon = !settings.confirm_abandon,
+            SettingsButton::MoveQueue => {
+                let index = MOVE_QUEUE_LENGTHS
+                    .iter()
+                    .position(|length| *length == settings.move_queue)
+                    .map_or(0, |index| (index + 1) % MOVE_QUEUE_LENGTHS.len());
+                settings.move_queue = MOVE_QUEUE_LENGTHS[index];
+            }
+            SettingsButton::Defaults => {
+                *settings = Settings::default();
+                themes.active = settings.theme.clone();
+            }
+            SettingsButton::Close => {
+                screen.open = false;
+                continue;
+            }
+        }
+        save_settings(&settings);
+        screen.message.clear();
+        *global_volume = GlobalVolume::new(volume_level(&settings));
+        move_queue.max_len = settings.move_queue;
+        // The game being played keeps its setup, a loaded game or puzzle waiting to start too
+        if next_game.mode == GameMode::Classic && !next_game.one_off {
+            next_game.board_size = settings.board_size;
+            next_game.four_chance = settings.four_chance;
+        }
+        menu.board_size = settings.board_size;
+    }
+}
+
+fn render_settings_screen(
+    mut commands: Commands,
+    screen: Res<SettingsScreen>,
+    settings: Res<Settings>,
+    animation_settings: Res<AnimationSettings>,
+    mut root_query: Query<&mut Style, With<SettingsScreenRoot>>,
+    list_query: Query<Entity, With<SettingsList>>,
+    font_spec: Res<FontSpec>,
+) {
+    if !screen.is_changed() && !settings.is_changed() && !animation_settings.is_changed() {
+        return;
+    }
+    root_query.single_mut().display = if screen.open {
+        Display::Flex
+    } else {
+        Display::None
+    };
+
+    let four_chance = if settings.four_chance > 0.0 {
+        format!("New tiles: {:.0}% fours", settings.four_chance * 100.0)
+    } else {
+        "New tiles: twos only".to_string()
+    };
+    let undo_limit = match settings.undo_limit {
+        Some(limit) => format!("Undos per game: {}", limit),
+        None => "Undos per game: unlimited".to_string(),
+    };
+    let rows = [
+        (format!("Board size: {0}x{0}", settings.board_size), SettingsButton::BoardSize),
+        (four_chance, SettingsButton::FourChance),
+        (
+            format!("Animation speed: {:?}", animation_settings.speed),
+            SettingsButton::AnimationSpeed,
+        ),
+        (format!("Easing: {:?}", animation_settings.easing), SettingsButton::Easing),
+        (
+            format!("Reduced motion: {}", if animation_settings.reduced_motion { "on" } else { "off" }),
+            SettingsButton::ReducedMotion,
+        ),
+        (format!("Theme: {}", settings.theme), SettingsButton::Theme),
+        ("Key bindings".to_string(), SettingsButton::KeyBindings),
+        (format!("Volume: {}%", settings.volume), SettingsButton::Volume),
+        (undo_limit, SettingsButton::UndoLimit),
+        (
+            format!("Confirm ending games: {}", if settings.confirm_abandon { "on" } else { "off" }),
+            SettingsButton::ConfirmAbandon,
+        ),
+        (format!("Queued moves: {}", settings.move_queue), SettingsButton::MoveQueue),
+        ("Defaults".to_string(), SettingsButton::Defaults),
+        ("Close".to_string(), SettingsButton::Close),
+    ];
+    let list = list_query.single();
+    commands.entity(list).despawn_descendants();
+    commands.entity(list).with_children(|parent| {
+        for (label, button) in rows {
+            spawn_button(parent, &font_spec, label, button, small_button_style());
+        }
+        parent.spawn((
+            TextBundle::from_section(
+                format!("Board size and new tiles apply to the next game\n{}", screen.message),
+                TextStyle {
+                    font: font_spec.family.clone(),
+                    font_size: 15.0,
+                    color: Color::WHITE,
+                },
+            ),
+            ThemeColor::Text,
+        ));
+    });
+}
+
+/// Spawns a themed button with its label, `marker` tells what it does.
+pub fn spawn_button(
+    parent: &mut ChildBuilder,
+    font_spec: &FontSpec,
+    label: impl Into<String>,
+    marker: impl Component,
+    style: ButtonStyle,
+) {
+    parent
+        .spawn((
+            NodeBundle {
+                style: style.node,
+                ..default()
+            },
+            Interaction::default(),
+            ThemeColor::Button,
+            marker,
+        ))
+        .with_children(|parent| {
+            parent.spawn((
+                TextBundle::from_section(
+                    label,
+                    TextStyle {
+                        font: font_spec.family.clone(),
+                        font_size: style.font_size,
+                        color: Color::WHITE,
+                    },
+                ),
+                ThemeColor::ButtonText,
+            ));
+        });
+}
+
+fn setup_settings_screen(mut commands: Commands, font_spec: Res<FontSpec>) {
+    commands
+        .spawn((
+            // Above the menus, below the key bindings it opens
+            overlay_root(8),
+            SettingsScreenRoot,
+        ))
+        .with_children(|parent| {
+            parent
+                .spawn((
+                    NodeBundle {
+                        style: Style {
+                            flex_direction: FlexDirection::Column,
+                            row_gap: Val::Px(5.0),
+                            padding: UiRect::all(Val::Px(20.0)),
+                            ..default()
+                        },
+                        ..default()
+                    },
+                    ThemeColor::Board,
+                ))
+                .with_children(|parent| {
+                    parent.spawn((
+                        TextBundle::from_section(
+                            "Settings",
                             TextStyle {
                                 font: font_spec.family.clone(),
                                 font_size: 20.0,
-                                color: Color::BLACK,
+                                color: Color::WHITE,
                             },
                         ),
-                        ..default()
-                    });
+                        ThemeColor::Text,
+                    ));
+                    parent.spawn((
+                        NodeBundle {
+                            style: Style {
+                                flex_direction: FlexDirection::Column,
+                                row_gap: Val::Px(5.0),
+                                ..default()
+                            },
+                            ..default()
+                        },
+                        SettingsList,
+                    ));
                 });
         });
 }
@@ -1,15 +1,23 @@
-use crate::colors::{BOARD, TILE, TILE_PLACEHODER};
+use crate::animation::{
+    Absorbed, AnimationSettings, PendingAnimation, ScorePopupEvent, TileAnimation, TileScale,
+};
+use crate::bindings::ActionEvent;
+use crate::layout::BoardArea;
+use crate::skins::TileSkin;
+use crate::theme::{Theme, ThemeColor};
 use crate::components::{
-    Board, FontSpec, Points,
+    Board, Cell, FontSpec, Points,
     Position, TileText, 
-    Game, TILE_SIZE, RunState
+    Game, TILE_SIZE, RunState,
+    GameRng, NextGame, SpawnQueue,
 };
 use bevy::prelude::*;
 use bevy_easings::*;
 use itertools::Itertools;
 use rand::prelude::*;
+use serde::{Deserialize, Serialize};
 use std::{
-    cmp::Ordering, collections::HashMap, convert::TryFrom,
+    cmp::Ordering, collections::{HashMap, VecDeque},
     ops::Range,
 };
 
@@ -17,63 +25,109 @@ pub fn setup(mut commands: Commands) {
     commands.spawn(Camera2dBundle::default());
 }
 
-pub fn spawn_board(mut commands: Commands) {
-    let board = Board::new(4);
+pub fn spawn_board(
+    mut commands: Commands,
+    next_game: Res<NextGame>,
+    area: Res<BoardArea>,
+    boards: Query<(Entity, &Board)>,
+) {
+    if let Ok((entity, board)) = boards.get_single() {
+        if board.size == next_game.board_size {
+            return;
+        }
+        commands.entity(entity).despawn_recursive();
+    }
+    let board = Board::new(next_game.board_size, area.0);
 
     commands
-        .spawn(SpriteBundle {
-            sprite: Sprite {
-                color: BOARD,
-                custom_size: Some(Vec2::new(board.physical_size, board.physical_size)),
+        .spawn((
+            SpriteBundle {
+                sprite: Sprite {
+                    custom_size: Some(Vec2::new(board.physical_size, board.physical_size)),
+                    ..default()
+                },
                 ..default()
             },
-            ..default()
-        })
+            ThemeColor::Board,
+        ))
         .with_children(|builder| {
             // Move the tile at center of board to the left bottom
             for tile in (0..board.size).cartesian_product(0..board.size) {
-                builder.spawn(SpriteBundle {
-                    sprite: Sprite {
-                        color: TILE_PLACEHODER,
-                        custom_size: Some(Vec2::new(TILE_SIZE, TILE_SIZE)),
+                builder.spawn((
+                    SpriteBundle {
+                        sprite: Sprite {
+                            custom_size: Some(Vec2::splat(board.tile_size)),
+                            ..default()
+                        },
+                        transform: Transform::from_xyz(
+                            board.cell_position_to_physical(tile.0),
+                            board.cell_position_to_physical(tile.1),
+                            1.0,
+                        ),
                         ..default()
                     },
-                    transform: Transform::from_xyz(
-                        board.cell_position_to_physical(tile.0),
-                        board.cell_position_to_physical(tile.1),
-                        1.0,
-                    ),
-                    ..default()
-                });
+                    ThemeColor::EmptyCell,
+                    Cell { x: tile.0, y: tile.1 },
+                ));
             }
         })
         .insert(board);
 }
 
-pub fn spawn_tiles(mut commands: Commands, query_board: Query<&Board>, font_spec: Res<FontSpec>) {
+pub fn spawn_tiles(
+    mut commands: Commands,
+    query_board: Query<&Board>,
+    font_spec: Res<FontSpec>,
+    mut rng: ResMut<GameRng>,
+    next_game: Res<NextGame>,
+) {
     let board = query_board.single();
-    let mut rng = rand::thread_rng();
+    if !next_game.tiles.is_empty() {
+        for (pos, points) in next_game.tiles.iter() {
+            spawn_tile(&mut commands, board, &font_spec, *pos, *points);
+        }
+        return;
+    }
     let starting_tiles: Vec<(u8, u8)> = (0..board.size)
         .cartesian_product(0..board.size)
-        .choose_multiple(&mut rng, 2);
+        .choose_multiple(&mut rng.0, 2);
 
     for (x, y) in starting_tiles.iter() {
         let pos = Position { x: *x, y: *y };
-        spawn_tile(&mut commands, board, &font_spec, pos);
+        let points = new_tile_points(&mut rng.0, next_game.four_chance);
+        spawn_tile(&mut commands, board, &font_spec, pos, points);
+    }
+}
+
+// Games without fours don't draw from the rng,
+// so they spawn the same tiles as before fours existed.
+fn new_tile_points(rng: &mut StdRng, four_chance: f32) -> Points {
+    if four_chance > 0.0 && rng.gen_bool(f64::from(four_chance)) {
+        Points { value: 4 }
+    } else {
+        Points { value: 2 }
     }
 }
 
+/// Font size that fits the digits of `value` inside a tile of `tile_size`,
+/// assuming a digit is about 0.6 times as wide as the font size.
+pub fn tile_font_size(value: u32, tile_size: f32) -> f32 {
+    let digits = value.to_string().len() as f32;
+    (0.3 * tile_size).min(0.8 * tile_size / (0.6 * digits))
+}
+
 pub fn spawn_tile(
     commands: &mut Commands,
     board: &Board,
     font_spec: &Res<FontSpec>,
     pos: Position,
-) {
+    points: Points,
+) -> Entity {
+    // Colored by render_tile_colors
     commands
         .spawn(SpriteBundle {
             sprite: Sprite {
-                color: TILE,
-                custom_size: Some(Vec2::new(TILE_SIZE, TILE_SIZE)),
+                custom_size: Some(Vec2::splat(board.tile_size)),
                 ..Sprite::default()
             },
             transform: Transform::from_xyz(
@@ -87,11 +141,11 @@ pub fn spawn_tile(
             builder
                 .spawn(Text2dBundle {
                     text: Text::from_section(
-                        "2",
+                        points.value.to_string(),
                         TextStyle {
                             font: font_spec.family.clone(),
-                            font_size: 30.0,
-                            color: Color::BLACK,
+                            font_size: tile_font_size(points.value, board.tile_size),
+                            ..default()
                         },
                     )
                     .with_alignment(TextAlignment::Center),
@@ -99,27 +153,67 @@ pub fn spawn_tile(
                     ..Default::default()
                 })
                 .insert(TileText);
+            // Shown by render_tile_skins when the active skin has a texture for the value
+            builder.spawn((
+                SpriteSheetBundle {
+                    sprite: TextureAtlasSprite {
+                        custom_size: Some(Vec2::splat(board.tile_size)),
+                        ..default()
+                    },
+                    transform: Transform::from_xyz(0.0, 0.0, 0.5),
+                    visibility: Visibility::Hidden,
+                    ..default()
+                },
+                TileSkin,
+            ));
         })
-        .insert(Points { value: 2 })
-        .insert(pos);
+        .insert(points)
+        .insert(pos)
+        .insert(TileScale::default())
+        .id()
 }
 pub fn render_tile_points(
     mut texts: Query<&mut Text, With<TileText>>,
-    tiles: Query<(&Points, &Children)>,
+    tiles: Query<(&Points, &Sprite, &Children)>,
 ) {
-    for (points, children) in tiles.iter() {
+    for (points, sprite, children) in tiles.iter() {
         if let Some(entity) = children.first() {
+            let tile_size = sprite.custom_size.map_or(TILE_SIZE, |size| size.x);
             let mut text = texts.get_mut(*entity).expect("Expected Text to exist");
             let mut text_section = text
                 .sections
                 .first_mut()
                 .expect("Expect first section to be accessible as mutable.");
-            text_section.value = points.value.to_string()
+            text_section.value = points.value.to_string();
+            text_section.style.font_size = tile_font_size(points.value, tile_size);
+        }
+    }
+}
+
+// Runs in PostUpdate, so tiles spawned during the frame are colored before they're drawn.
+pub fn render_tile_colors(
+    theme: Res<Theme>,
+    mut texts: Query<&mut Text, With<TileText>>,
+    mut tiles: Query<(Ref<Points>, &mut Sprite, &Children)>,
+) {
+    for (points, mut sprite, children) in tiles.iter_mut() {
+        if !points.is_changed() && !theme.is_changed() {
+            continue;
+        }
+        let colors = theme.tile(points.value);
+        sprite.color = colors.tile;
+        for child in children.iter() {
+            if let Ok(mut text) = texts.get_mut(*child) {
+                for section in text.sections.iter_mut() {
+                    section.style.color = colors.text;
+                }
+            }
         }
     }
 }
 
 //----------------------------------------------------------------
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum BoardShift {
     Left,
     Right,
@@ -147,13 +241,24 @@ impl BoardShift {
             },
         }
     }
-    fn set_column_position(&self, board_size: u8, position: &mut Mut<Position>, index: u8) {
+    pub const ALL: [BoardShift; 4] = [
+        BoardShift::Left,
+        BoardShift::Right,
+        BoardShift::Up,
+        BoardShift::Down,
+    ];
+
+    fn column_position(&self, board_size: u8, mut position: Position, index: u8) -> Position {
         match self {
             BoardShift::Left => position.x = index,
             BoardShift::Right => position.x = board_size - index - 1,
             BoardShift::Up => position.y = board_size - index - 1,
             BoardShift::Down => position.y = index,
         }
+        position
+    }
+    fn set_column_position(&self, board_size: u8, position: &mut Mut<Position>, index: u8) {
+        **position = self.column_position(board_size, **position, index);
     }
     fn get_row_position(&self, position: &Position) -> u8 {
         match self {
@@ -161,38 +266,107 @@ impl BoardShift {
             BoardShift::Up | BoardShift::Down => position.x,
         }
     }
+
+    /// Points the shift would score on these tiles, `None` when no tile would move.
+    pub fn points(&self, board_size: u8, tiles: &[(Position, u32)]) -> Option<u32> {
+        let mut points = 0;
+        let mut moved = false;
+        let rows = tiles
+            .iter()
+            .sorted_by(|a, b| self.sort(&a.0, &b.0))
+            .group_by(|(position, _)| self.get_row_position(position));
+        for (_, row) in rows.into_iter() {
+            let mut row = row.peekable();
+            let mut column: u8 = 0;
+            while let Some((position, value)) = row.next() {
+                if row.peek().is_some_and(|(_, next)| next == value) {
+                    row.next();
+                    points += value * 2;
+                    moved = true;
+                }
+                moved |= *position != self.column_position(board_size, *position, column);
+                column += 1;
+            }
+        }
+        moved.then_some(points)
+    }
 }
-impl TryFrom<&KeyCode> for BoardShift {
-    type Error = &'static str;
 
-    fn try_from(value: &KeyCode) -> Result<Self, Self::Error> {
-        match value {
-            KeyCode::Left => Ok(BoardShift::Left),
-            KeyCode::Right => Ok(BoardShift::Right),
-            KeyCode::Up => Ok(BoardShift::Up),
-            KeyCode::Down => Ok(BoardShift::Down),
-            _ => Err("Not a valid board shift key"),
+/// Shifts waiting to be applied, one per finished move.
+#[derive(Resource)]
+pub struct MoveQueue {
+    pub moves: VecDeque<BoardShift>,
+    /// Shifts beyond this many are dropped.
+    pub max_len: usize,
+    /// Jump running animations to their end instead of waiting for them.
+    pub snap_animations: bool,
+}
+
+impl Default for MoveQueue {
+    fn default() -> Self {
+        MoveQueue {
+            moves: VecDeque::new(),
+            max_len: 4,
+            snap_animations: false,
         }
     }
 }
+
+pub fn queue_moves(mut actions: EventReader<ActionEvent>, mut queue: ResMut<MoveQueue>) {
+    for ActionEvent(action) in actions.iter() {
+        if let Some(board_shift) = action.board_shift() {
+            if queue.moves.len() < queue.max_len {
+                queue.moves.push_back(board_shift);
+            }
+        }
+    }
+}
+
+pub fn snap_animations(
+    mut commands: Commands,
+    queue: Res<MoveQueue>,
+    mut tiles: Query<(Entity, &mut Transform, &Position), With<EasingComponent<Transform>>>,
+    absorbed: Query<Entity, With<Absorbed>>,
+    query_board: Query<&Board>,
+) {
+    if !queue.snap_animations || queue.moves.is_empty() {
+        return;
+    }
+    for entity in absorbed.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    let board = query_board.single();
+    for (entity, mut transform, pos) in tiles.iter_mut() {
+        transform.translation.x = board.cell_position_to_physical(pos.x);
+        transform.translation.y = board.cell_position_to_physical(pos.y);
+        commands.entity(entity).remove::<EasingComponent<Transform>>();
+    }
+}
+
 pub fn board_shift(
     mut commands: Commands,
-    input: Res<Input<KeyCode>>,
-    mut tiles: Query<(Entity, &mut Position, &mut Points)>,
+    mut queue: ResMut<MoveQueue>,
+    animating: Query<(), With<EasingComponent<Transform>>>,
+    mut tiles: Query<(Entity, &mut Position, &mut Points, &Transform, &Children)>,
     query_board: Query<&Board>,
     mut tile_writer: EventWriter<NewTileEvent>,
     mut game: ResMut<Game>,
+    settings: Res<AnimationSettings>,
+    mut popup_writer: EventWriter<ScorePopupEvent>,
 ) {
     let board = query_board.single();
-    let shift_direction = input
-        .get_just_pressed()
-        .find_map(|key_code| BoardShift::try_from(key_code).ok());
-    if let Some(board_shift) = shift_direction {
+    // The previous move has to finish sliding first
+    if !animating.is_empty() {
+        return;
+    }
+    if let Some(board_shift) = queue.moves.pop_front() {
         let mut iter = tiles
             .iter_mut()
             .sorted_by(|a, b| board_shift.sort(&a.1, &b.1))
             .peekable();
         let mut column: u8 = 0;
+        let mut merges: Vec<Vec2> = Vec::new();
+        let mut points: u32 = 0;
         while let Some(mut tile) = iter.next() {
             board_shift.set_column_position(board.size, &mut tile.1, column);
             if let Some(tile_next) = iter.peek() {
@@ -209,7 +383,38 @@ pub fn board_shift(
                         .expect("A peeked tile should always exist when we .next here");
                     tile.2.value = tile.2.value + real_next_tile.2.value;
                     game.score += tile.2.value;
-                    commands.entity(real_next_tile.0).despawn_recursive();
+                    points += tile.2.value;
+                    // The absorbed tile slides under its partner and is removed after,
+                    // without Position and Points it no longer counts as a tile.
+                    let destination = Transform::from_xyz(
+                        board.cell_position_to_physical(tile.1.x),
+                        board.cell_position_to_physical(tile.1.y),
+                        1.5,
+                    );
+                    merges.push(destination.translation.truncate());
+                    if settings.instant() {
+                        commands.entity(real_next_tile.0).despawn_recursive();
+                    } else {
+                        commands
+                            .entity(real_next_tile.0)
+                            .remove::<(Position, Points)>()
+                            .insert(Absorbed::after_slide(&settings))
+                            .insert(real_next_tile.3.ease_to(
+                                destination,
+                                settings.easing.method(),
+                                EasingType::Once {
+                                    duration: settings.slide(),
+                                },
+                            ));
+                        for child in real_next_tile.4.iter() {
+                            commands.entity(*child).insert(Transform::from_xyz(0.0, 0.0, 0.4));
+                        }
+                    }
+                    if settings.flourishes() {
+                        commands
+                            .entity(tile.0)
+                            .insert(PendingAnimation::after_slide(TileAnimation::Merge, &settings));
+                    }
                     if let Some(future) = iter.peek() {
                         if board_shift.get_row_position(&tile.1)
                             != board_shift.get_row_position(&future.1)
@@ -222,6 +427,15 @@ pub fn board_shift(
                 }
             }
         }
+        if !merges.is_empty() {
+            // One popup per move, in the middle of its merges
+            popup_writer.send(ScorePopupEvent {
+                points,
+                position: merges.iter().sum::<Vec2>() / merges.len() as f32,
+            });
+        }
+        game.moves += 1;
+        game.shifts.push(board_shift);
         tile_writer.send(NewTileEvent);
         if game.best_score < game.score {
             game.best_score = game.score;
@@ -234,23 +448,27 @@ pub fn render_tiles(
     mut commands: Commands,
     mut tiles: Query<(Entity, &mut Transform, &Position), Changed<Position>>,
     query_board: Query<&Board>,
+    settings: Res<AnimationSettings>,
 ) {
     let board = query_board.single();
-    for (entity, transform, pos) in tiles.iter_mut() {
+    for (entity, mut transform, pos) in tiles.iter_mut() {
         let x = board.cell_position_to_physical(pos.x);
         let y = board.cell_position_to_physical(pos.y);
 
+        if settings.instant() {
+            transform.translation.x = x;
+            transform.translation.y = y;
+            continue;
+        }
         commands.entity(entity).insert(transform.ease_to(
             Transform::from_xyz(
                 x,
                 y,
                 transform.translation.z,
             ),
-            EaseFunction::QuadraticInOut,
+            settings.easing.method(),
             EasingType::Once {
-                duration: std::time::Duration::from_millis(
-                    100,
-                ),
+                duration: settings.slide(),
             },
         ));
     }
@@ -259,16 +477,44 @@ pub fn render_tiles(
 #[derive(Event)]
 pub struct NewTileEvent;
 
+#[derive(Event)]
+pub struct NewGameEvent;
+
 pub fn new_tile_handler(
     mut tile_reader: EventReader<NewTileEvent>,
     mut commands: Commands,
     query_board: Query<&Board>,
     tiles: Query<&Position>,
     font_spec: Res<FontSpec>,
+    mut rng: ResMut<GameRng>,
+    mut spawn_queue: ResMut<SpawnQueue>,
+    game: Res<Game>,
+    settings: Res<AnimationSettings>,
 ) {
     let board = query_board.single();
+    // New tiles stay hidden until the shift finished sliding, then pop in
+    let pop_in = |commands: &mut Commands, entity: Entity| {
+        if !settings.instant() {
+            commands.entity(entity).insert((
+                TileScale { grow: 0.0, pulse: 0.0 },
+                PendingAnimation::after_slide(TileAnimation::Spawn, &settings),
+            ));
+        }
+    };
     for _event in tile_reader.iter() {
-        let mut rng = rand::thread_rng();
+        if let Some((pos, points)) = spawn_queue.0.pop_front() {
+            // A random tile instead would break the fixed sequence
+            if tiles.iter().any(|&tile_pos| tile_pos == pos) {
+                warn!(
+                    "The queued {} can't spawn at ({}, {}), a tile is already there",
+                    points.value, pos.x, pos.y
+                );
+            } else {
+                let entity = spawn_tile(&mut commands, board, &font_spec, pos, points);
+                pop_in(&mut commands, entity);
+            }
+            continue;
+        }
         let possible_pos: Option<Position> = (0..board.size)
             .cartesian_product(0..board.size)
             .filter_map(|tile_pos| {
@@ -281,9 +527,11 @@ pub fn new_tile_handler(
                     None => Some(new_position),
                 }
             })
-            .choose(&mut rng);
+            .choose(&mut rng.0);
         if let Some(pos) = possible_pos {
-            spawn_tile(&mut commands, board, &font_spec, pos);
+            let points = new_tile_points(&mut rng.0, game.setup.four_chance);
+            let entity = spawn_tile(&mut commands, board, &font_spec, pos, points);
+            pop_in(&mut commands, entity);
         }
     }
 }
@@ -294,7 +542,7 @@ pub fn end_game(
     mut run_state: ResMut<NextState<RunState>>,
 ) {
     let board = query_board.single();
-    if tiles.iter().len() == 16 {
+    if tiles.iter().len() == usize::from(board.size) * usize::from(board.size) {
         let neighbor_points = [(-1,0), (1,0), (0,1), (0,-1)];
         let map: HashMap<&Position, &Points> = tiles.iter().collect();
         let board_range: Range<i8> = 0..(board.size as i8);
@@ -320,7 +568,6 @@ pub fn end_game(
             },
         );
         if !has_move {
-            dbg!("Game over");
             run_state.set(RunState::GameOver);
         }
     }
@@ -330,9 +577,53 @@ pub fn game_reset(
     mut commands: Commands,
     tiles: Query<Entity, With<Position>>,
     mut game: ResMut<Game>,
+    mut rng: ResMut<GameRng>,
+    mut spawn_queue: ResMut<SpawnQueue>,
+    mut move_queue: ResMut<MoveQueue>,
+    next_game: Res<NextGame>,
 ) {
+    move_queue.moves.clear();
     for entity in tiles.iter() {
         commands.entity(entity).despawn_recursive();
     }
-    game.score = 0;
+    game.score = next_game.score;
+    game.best_at_start = game.best_score;
+    game.moves = next_game.moves;
+    game.undos = 0;
+    game.hints = 0;
+    game.recorded = false;
+    game.clock.reset();
+    game.shifts.clear();
+    game.mode = next_game.mode;
+    game.seed = next_game.seed.unwrap_or_else(rand::random);
+    game.setup = NextGame {
+        seed: Some(game.seed),
+        one_off: true,
+        ..next_game.clone()
+    };
+    rng.0 = StdRng::seed_from_u64(game.seed);
+    spawn_queue.0 = next_game.spawns.clone();
+}
+
+// Runs once the one-off setup was used by spawn_tiles.
+pub fn forget_one_off_game(mut next_game: ResMut<NextGame>) {
+    if next_game.one_off {
+        *next_game = NextGame {
+            board_size: next_game.board_size,
+            four_chance: next_game.four_chance,
+            ..default()
+        };
+    }
+}
+
+pub fn new_game(mut new_game_writer: EventWriter<NewGameEvent>) {
+    new_game_writer.send(NewGameEvent);
+}
+
+pub fn start_playing(mut run_state: ResMut<NextState<RunState>>) {
+    run_state.set(RunState::Playing);
+}
+
+pub fn tick_game_clock(time: Res<Time>, mut game: ResMut<Game>) {
+    game.clock.tick(time.delta());
 }
\ No newline at end of file